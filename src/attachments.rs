@@ -0,0 +1,364 @@
+use chrono::{DateTime, Utc};
+use cocoon::Cocoon;
+use console::Term;
+use data_encoding::HEXLOWER;
+use dialoguer::{theme::ColorfulTheme, Input, Select};
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::backups::list_snapshots;
+use crate::config::{vault_sibling_path, MAX_ATTACHMENT_SIZE};
+use crate::entry::Entry;
+use crate::languages::get_translation;
+use crate::lock::is_read_only;
+
+/// A file attached to an entry.
+///
+/// Its content is encrypted with its own random key, kept in the vault, and stored
+/// next to the vault in a blob named after the SHA-256 of the encrypted content.
+/// Changing the master password therefore leaves attachments untouched.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub name: String,
+    pub size: u64,
+    pub blob: String,
+    pub key: String,
+    pub added_at: DateTime<Utc>,
+}
+
+pub fn attachments_directory() -> PathBuf {
+    vault_sibling_path(".attachments")
+}
+
+/// Encrypts `content` into a new blob of `directory`.
+fn store_blob(directory: &Path, name: &str, content: &[u8]) -> Result<Attachment, Box<dyn Error>> {
+    if content.len() as u64 > MAX_ATTACHMENT_SIZE {
+        return Err(format!(
+            "{} is larger than the {} KiB allowed for attachments",
+            name,
+            MAX_ATTACHMENT_SIZE / 1024
+        )
+        .into());
+    }
+
+    let mut key = [0u8; 32];
+    thread_rng().fill_bytes(&mut key);
+    let key = HEXLOWER.encode(&key);
+
+    let encrypted = match Cocoon::new(key.as_bytes()).wrap(content) {
+        Ok(encrypted) => encrypted,
+        Err(error) => return Err(format!("Cannot encrypt {}: {:?}", name, error).into()),
+    };
+    let blob = HEXLOWER.encode(&Sha256::digest(&encrypted));
+
+    fs::create_dir_all(directory)?;
+    let temp_path = directory.join(format!("{}.tmp", blob));
+    let mut file = File::create(&temp_path)?;
+    file.write_all(&encrypted)?;
+    file.sync_all()?;
+    fs::rename(&temp_path, directory.join(&blob))?;
+
+    Ok(Attachment {
+        name: name.to_string(),
+        size: content.len() as u64,
+        blob,
+        key,
+        added_at: Utc::now(),
+    })
+}
+
+fn load_blob(directory: &Path, attachment: &Attachment) -> Result<Vec<u8>, Box<dyn Error>> {
+    let encrypted = match fs::read(directory.join(&attachment.blob)) {
+        Ok(encrypted) => encrypted,
+        Err(_) => return Err(format!("The content of {} is missing", attachment.name).into()),
+    };
+
+    if HEXLOWER.encode(&Sha256::digest(&encrypted)) != attachment.blob {
+        return Err(format!("The content of {} is corrupted", attachment.name).into());
+    }
+
+    match Cocoon::new(attachment.key.as_bytes()).unwrap(&encrypted) {
+        Ok(content) => Ok(content),
+        Err(_) => Err(format!("The content of {} is corrupted", attachment.name).into()),
+    }
+}
+
+pub fn add_attachment(entry: &mut Entry, path: &Path) -> Result<(), Box<dyn Error>> {
+    // Checked before reading the file, which may be huge.
+    if fs::metadata(path)?.len() > MAX_ATTACHMENT_SIZE {
+        return Err(format!(
+            "{} is larger than the {} KiB allowed for attachments",
+            path.display(),
+            MAX_ATTACHMENT_SIZE / 1024
+        )
+        .into());
+    }
+
+    let name = match path.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => return Err(format!("{} is not a file", path.display()).into()),
+    };
+
+    if entry.attachments.iter().any(|a| a.name == name) {
+        return Err(format!("{} is already attached to this entry", name).into());
+    }
+
+    let attachment = store_blob(&attachments_directory(), &name, &fs::read(path)?)?;
+    entry.attachments.push(attachment);
+
+    Ok(())
+}
+
+/// Writes the content of an attachment to `destination`, or to a file of that name in
+/// `destination` when it is a directory. Existing files are never overwritten.
+pub fn extract_attachment(
+    attachment: &Attachment,
+    destination: &Path,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let content = load_blob(&attachments_directory(), attachment)?;
+
+    let path = if destination.is_dir() {
+        destination.join(&attachment.name)
+    } else {
+        destination.to_path_buf()
+    };
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    file.write_all(&content)?;
+
+    Ok(path)
+}
+
+/// Removes an attachment from an entry. Its content is deleted by
+/// `collect_unused_blobs` once the vault is saved, unless a snapshot still refers
+/// to it.
+pub fn remove_attachment(entry: &mut Entry, attachment_index: usize) -> Attachment {
+    entry.attachments.remove(attachment_index)
+}
+
+fn referenced_blobs(entries: &[Entry]) -> HashSet<String> {
+    entries
+        .iter()
+        .flat_map(|e| &e.attachments)
+        .map(|a| a.blob.clone())
+        .collect()
+}
+
+// Blobs of `directory` missing from `referenced`. Temporary files left by a crash are
+// not blobs, they are left alone.
+fn unreferenced_blobs(directory: &Path, referenced: &HashSet<String>) -> io::Result<Vec<String>> {
+    let dir_entries = match fs::read_dir(directory) {
+        Ok(dir_entries) => dir_entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+
+    let mut blobs = Vec::new();
+    for dir_entry in dir_entries {
+        let name = dir_entry?.file_name().to_string_lossy().to_string();
+        let is_blob = name.len() == 64 && name.bytes().all(|b| b.is_ascii_hexdigit());
+
+        if is_blob && !referenced.contains(&name) {
+            blobs.push(name);
+        }
+    }
+
+    Ok(blobs)
+}
+
+/// Deletes the blobs that neither the vault nor any of its snapshots refer to, once
+/// the vault is saved and its old snapshots pruned.
+pub fn collect_unused_blobs(entries: &[Entry], password: &str) -> Result<(), Box<dyn Error>> {
+    let directory = attachments_directory();
+    let mut unused = unreferenced_blobs(&directory, &referenced_blobs(entries))?;
+
+    for snapshot in list_snapshots()? {
+        if unused.is_empty() {
+            return Ok(());
+        }
+
+        match snapshot.read_entries(password) {
+            Ok(snapshot_entries) => {
+                let referenced = referenced_blobs(&snapshot_entries);
+                unused.retain(|blob| !referenced.contains(blob));
+            }
+            // Snapshots locked with an older master password may refer to any of
+            // them. They are collected once these snapshots are pruned.
+            Err(_) => return Ok(()),
+        }
+    }
+
+    for blob in unused {
+        fs::remove_file(directory.join(blob))?;
+    }
+
+    Ok(())
+}
+
+pub fn describe_attachment(attachment: &Attachment) -> String {
+    format!(
+        "{}  ·  {:.1} KiB",
+        attachment.name,
+        attachment.size as f64 / 1024.0
+    )
+}
+
+/// Attachments screen of the interactive interface. Returns whether the entry changed.
+pub fn manage_attachments(entries: &mut [Entry], index: usize) -> Result<bool, Box<dyn Error>> {
+    let mut changed = false;
+
+    loop {
+        println!(
+            "\n  {} {}\n",
+            get_translation("attachments_of"),
+            entries[index].application
+        );
+
+        let mut items: Vec<String> = entries[index]
+            .attachments
+            .iter()
+            .map(describe_attachment)
+            .collect();
+        let count = items.len();
+        // Another instance of NeoPass is the only one allowed to write this vault.
+        if !is_read_only() {
+            items.push(get_translation("add_attachment"));
+        }
+
+        if items.is_empty() {
+            println!("  {}", get_translation("no_attachments"));
+            Term::stderr().read_key()?;
+            return Ok(changed);
+        }
+
+        let selection = match Select::with_theme(&ColorfulTheme::default())
+            .default(0)
+            .items(&items)
+            .interact_opt()?
+        {
+            Some(selection) => selection,
+            None => return Ok(changed),
+        };
+
+        if selection == count {
+            let path: String = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("  {}", get_translation("enter_attachment_path")))
+                .interact_text()?;
+
+            match add_attachment(&mut entries[index], Path::new(path.trim())) {
+                Ok(()) => changed = true,
+                Err(error) => println!("\n  ❌ {}", error),
+            }
+            continue;
+        }
+
+        let mut actions = vec![get_translation("extract_attachment")];
+        if !is_read_only() {
+            actions.push(get_translation("delete_attachment"));
+        }
+
+        let action = match Select::with_theme(&ColorfulTheme::default())
+            .default(0)
+            .items(&actions)
+            .interact_opt()?
+        {
+            Some(action) => action,
+            None => continue,
+        };
+
+        if action == 0 {
+            let destination: String = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("  {}", get_translation("enter_destination")))
+                .with_initial_text(".")
+                .interact_text()?;
+
+            match extract_attachment(
+                &entries[index].attachments[selection],
+                Path::new(destination.trim()),
+            ) {
+                Ok(path) => println!(
+                    "\n  {} {}",
+                    get_translation("attachment_extracted"),
+                    path.display()
+                ),
+                Err(error) => println!("\n  ❌ {}", error),
+            }
+        } else {
+            remove_attachment(&mut entries[index], selection);
+            changed = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_directory() -> PathBuf {
+        std::env::temp_dir().join(format!("neopass-attachments-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_blob_round_trip() {
+        let directory = temp_directory();
+
+        let attachment = store_blob(&directory, "codes.txt", b"1234 5678").unwrap();
+
+        assert_eq!(attachment.size, 9);
+        assert!(directory.join(&attachment.blob).exists());
+        assert_eq!(load_blob(&directory, &attachment).unwrap(), b"1234 5678");
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_corrupted_blob() {
+        let directory = temp_directory();
+
+        let attachment = store_blob(&directory, "codes.txt", b"1234 5678").unwrap();
+        fs::write(directory.join(&attachment.blob), b"tampered").unwrap();
+
+        assert!(load_blob(&directory, &attachment).is_err());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_unreferenced_blobs() {
+        let directory = temp_directory();
+
+        let kept = store_blob(&directory, "kept.txt", b"kept").unwrap();
+        let removed = store_blob(&directory, "removed.txt", b"removed").unwrap();
+        fs::write(directory.join(format!("{}.tmp", removed.blob)), b"").unwrap();
+
+        let referenced = HashSet::from([kept.blob]);
+
+        assert_eq!(
+            unreferenced_blobs(&directory, &referenced).unwrap(),
+            vec![removed.blob]
+        );
+        assert!(unreferenced_blobs(&directory.join("missing"), &referenced)
+            .unwrap()
+            .is_empty());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_size_limit() {
+        let directory = temp_directory();
+        let content = vec![0; MAX_ATTACHMENT_SIZE as usize + 1];
+
+        assert!(store_blob(&directory, "big.bin", &content).is_err());
+        assert!(!directory.exists());
+    }
+}
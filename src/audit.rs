@@ -0,0 +1,418 @@
+use chrono::{DateTime, Utc};
+use console::Term;
+use dialoguer::{theme::ColorfulTheme, Select};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::breaches::BreachCheck;
+use crate::entry::Entry;
+use crate::languages::get_translation;
+use crate::strength::{estimate, MIN_ACCEPTED_SCORE};
+use crate::trash::visible_entries;
+
+/// Problems found in the entries of a vault. Entries in the trash are left out.
+#[derive(Debug, Default, PartialEq)]
+pub struct AuditReport {
+    // Entries whose password appeared in breaches, with how many times.
+    pub breached: Vec<(usize, u64)>,
+    // Groups of entries sharing the same password.
+    pub reused: Vec<Vec<usize>>,
+    // Entries with their strength score.
+    pub weak: Vec<(usize, u8)>,
+    // Entries with the age of their password, in days.
+    pub old: Vec<(usize, i64)>,
+    // Groups of entries of the same account.
+    pub duplicates: Vec<Vec<usize>>,
+    pub without_otp: Vec<usize>,
+}
+
+impl AuditReport {
+    pub fn is_empty(&self) -> bool {
+        *self == AuditReport::default()
+    }
+}
+
+// Only real passwords are worth auditing, not card numbers or private keys.
+pub fn has_password(entry: &Entry) -> bool {
+    entry.kind.schema().secret == Some("password") && !entry.password.is_empty()
+}
+
+fn groups(keys: Vec<(String, usize)>) -> Vec<Vec<usize>> {
+    let mut groups: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (key, index) in keys {
+        groups.entry(key).or_default().push(index);
+    }
+
+    let mut groups: Vec<Vec<usize>> = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect();
+    groups.sort();
+    groups
+}
+
+/// `breached` are the entries found in a list of breached passwords, if any.
+pub fn audit(
+    entries: &[Entry],
+    breached: &BTreeMap<usize, u64>,
+    rotation_days: u64,
+    now: DateTime<Utc>,
+) -> AuditReport {
+    let visible = visible_entries(entries);
+    let with_password: Vec<usize> = visible
+        .iter()
+        .copied()
+        .filter(|index| has_password(&entries[*index]))
+        .collect();
+
+    let breached = with_password
+        .iter()
+        .filter_map(|index| Some((*index, *breached.get(index)?)))
+        .collect();
+
+    let reused = groups(
+        with_password
+            .iter()
+            .map(|index| (entries[*index].password.clone(), *index))
+            .collect(),
+    );
+
+    let weak = with_password
+        .iter()
+        .filter_map(|index| {
+            let entry = &entries[*index];
+            let strength = estimate(&entry.password, &[&entry.application, &entry.username]);
+            (strength.score < MIN_ACCEPTED_SCORE).then_some((*index, strength.score))
+        })
+        .collect();
+
+    let old = with_password
+        .iter()
+        .filter_map(|index| {
+            let age = (now - entries[*index].password_changed_at?).num_days();
+            (age >= rotation_days as i64).then_some((*index, age))
+        })
+        .collect();
+
+    let duplicates = groups(
+        visible
+            .iter()
+            .map(|index| {
+                let entry = &entries[*index];
+                (
+                    format!(
+                        "{}\n{}",
+                        entry.application.trim().to_lowercase(),
+                        entry.username.trim().to_lowercase()
+                    ),
+                    *index,
+                )
+            })
+            .collect(),
+    );
+
+    let without_otp = visible
+        .iter()
+        .copied()
+        .filter(|index| entries[*index].kind.is_login() && entries[*index].otp.is_none())
+        .collect();
+
+    AuditReport {
+        breached,
+        reused,
+        weak,
+        old,
+        duplicates,
+        without_otp,
+    }
+}
+
+fn describe_entry(entry: &Entry) -> String {
+    if entry.username.is_empty() {
+        entry.application.clone()
+    } else {
+        format!("{} ({})", entry.application, entry.username)
+    }
+}
+
+/// Sections of the report, with the entries they list and how to describe them.
+pub fn report_sections(
+    report: &AuditReport,
+    entries: &[Entry],
+    rotation_days: u64,
+) -> Vec<(String, Vec<(usize, String)>)> {
+    let strength_labels = ["strength_very_weak", "strength_weak"];
+
+    let grouped = |groups: &[Vec<usize>]| -> Vec<(usize, String)> {
+        groups
+            .iter()
+            .enumerate()
+            .flat_map(|(number, group)| {
+                group.iter().map(move |index| {
+                    (
+                        *index,
+                        format!("[{}] {}", number + 1, describe_entry(&entries[*index])),
+                    )
+                })
+            })
+            .collect()
+    };
+
+    let sections = vec![
+        (
+            get_translation("audit_breached"),
+            report
+                .breached
+                .iter()
+                .map(|(index, count)| {
+                    (
+                        *index,
+                        format!(
+                            "{}  ·  {} {}",
+                            describe_entry(&entries[*index]),
+                            count,
+                            get_translation("audit_times")
+                        ),
+                    )
+                })
+                .collect(),
+        ),
+        (get_translation("audit_reused"), grouped(&report.reused)),
+        (
+            get_translation("audit_weak"),
+            report
+                .weak
+                .iter()
+                .map(|(index, score)| {
+                    (
+                        *index,
+                        format!(
+                            "{}  ·  {}",
+                            describe_entry(&entries[*index]),
+                            get_translation(strength_labels[(*score as usize).min(1)])
+                        ),
+                    )
+                })
+                .collect(),
+        ),
+        (
+            format!(
+                "{} {} {}",
+                get_translation("audit_old"),
+                rotation_days,
+                get_translation("duration_days")
+            ),
+            report
+                .old
+                .iter()
+                .map(|(index, age)| {
+                    (
+                        *index,
+                        format!(
+                            "{}  ·  {} {}",
+                            describe_entry(&entries[*index]),
+                            age,
+                            get_translation("duration_days")
+                        ),
+                    )
+                })
+                .collect(),
+        ),
+        (
+            get_translation("audit_duplicates"),
+            grouped(&report.duplicates),
+        ),
+        (
+            get_translation("audit_without_otp"),
+            report
+                .without_otp
+                .iter()
+                .map(|index| (*index, describe_entry(&entries[*index])))
+                .collect(),
+        ),
+    ];
+
+    sections
+        .into_iter()
+        .filter(|(_, items)| !items.is_empty())
+        .collect()
+}
+
+/// Prints the report, for `neopass audit`.
+pub fn print_report(report: &AuditReport, entries: &[Entry], rotation_days: u64) {
+    if report.is_empty() {
+        println!("{}", get_translation("audit_all_good"));
+        return;
+    }
+
+    for (title, items) in report_sections(report, entries, rotation_days) {
+        println!("{} ({})", title, items.len());
+        for (_, description) in items {
+            println!("  {}", description);
+        }
+        println!();
+    }
+}
+
+/// Security audit screen. Returns the entry the user wants to jump to.
+pub fn display_audit(
+    entries: &[Entry],
+    breaches: &BreachCheck,
+    rotation_days: u64,
+) -> Result<Option<usize>, Box<dyn Error>> {
+    // The rest of the audit is still worth showing when the list cannot be read.
+    let (breached, breached_error) = match breaches {
+        Ok(breached) => (breached.clone(), None),
+        Err(error) => (BTreeMap::new(), Some(error)),
+    };
+
+    let report = audit(entries, &breached, rotation_days, Utc::now());
+    let sections = report_sections(&report, entries, rotation_days);
+
+    println!("\n  {}\n", get_translation("audit"));
+
+    if let Some(error) = breached_error {
+        println!(
+            "  ⚠ {}: {}\n",
+            get_translation("breached_list_unreadable"),
+            error
+        );
+    }
+
+    if sections.is_empty() {
+        println!("  {}", get_translation("audit_all_good"));
+        Term::stderr().read_key()?;
+        return Ok(None);
+    }
+
+    let mut section = 0;
+
+    loop {
+        let titles: Vec<String> = sections
+            .iter()
+            .map(|(title, items)| format!("{} ({})", title, items.len()))
+            .collect();
+
+        section = match Select::with_theme(&ColorfulTheme::default())
+            .default(section)
+            .items(&titles)
+            .interact_opt()?
+        {
+            Some(section) => section,
+            None => return Ok(None),
+        };
+
+        let items = &sections[section].1;
+        let descriptions: Vec<&String> = items.iter().map(|(_, description)| description).collect();
+
+        if let Some(selection) = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("  {}", get_translation("audit_jump_to_entry")))
+            .default(0)
+            .items(&descriptions)
+            .interact_opt()?
+        {
+            return Ok(Some(items[selection].0));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinds::EntryKind;
+    use crate::otp::Otp;
+    use chrono::Duration;
+
+    fn entry(application: &str, username: &str, password: &str) -> Entry {
+        Entry::new(
+            application.to_string(),
+            username.to_string(),
+            password.to_string(),
+        )
+    }
+
+    #[test]
+    fn test_reused_and_duplicates() {
+        let entries = vec![
+            entry("Mail", "john", "h7#Kq9!zP2@wLm4x"),
+            entry("Bank", "john", "h7#Kq9!zP2@wLm4x"),
+            entry("mail ", "John", "Vb3$nT8&kR5*yH1q"),
+            entry("Shop", "john", "Vb3$nT8&kR5*yH1r"),
+        ];
+
+        let report = audit(&entries, &BTreeMap::new(), 365, Utc::now());
+
+        assert_eq!(report.reused, vec![vec![0, 1]]);
+        assert_eq!(report.duplicates, vec![vec![0, 2]]);
+        assert!(report.weak.is_empty());
+        assert!(report.old.is_empty());
+    }
+
+    #[test]
+    fn test_weak_and_old() {
+        let now = Utc::now();
+        let mut entries = vec![
+            entry("Mail", "john", "password1"),
+            entry("Bank", "john", "h7#Kq9!zP2@wLm4x"),
+        ];
+        entries[1].password_changed_at = Some(now - Duration::days(400));
+
+        let report = audit(&entries, &BTreeMap::new(), 365, now);
+
+        assert_eq!(report.weak, vec![(0, 0)]);
+        assert_eq!(report.old, vec![(1, 400)]);
+    }
+
+    #[test]
+    fn test_old_without_history() {
+        let now = Utc::now();
+        let mut entries = vec![
+            entry("Mail", "john", "h7#Kq9!zP2@wLm4x"),
+            entry("Bank", "john", "Vb3$nT8&kR5*yH1q"),
+        ];
+        entries[0].created_at = Some(now - Duration::days(800));
+        entries[1].created_at = None;
+        for entry in &mut entries {
+            entry.password_changed_at = Some(now - Duration::days(400));
+        }
+
+        // Without history, the change of the password is still known.
+        entries[0].replace_password("zQ4!mW8#rT2&vN6p".to_string(), 0);
+
+        let report = audit(&entries, &BTreeMap::new(), 365, now);
+
+        assert_eq!(report.old, vec![(1, 400)]);
+    }
+
+    #[test]
+    fn test_only_visible_logins() {
+        let mut entries = vec![
+            entry("Mail", "john", "password1"),
+            entry("Bank", "john", "password1"),
+            entry("Card", "John Smith", "4970100000000000"),
+            entry("Code", "john", "h7#Kq9!zP2@wLm4x"),
+        ];
+        entries[1].deleted_at = Some(Utc::now());
+        entries[2].kind = EntryKind::Card;
+        entries[3].otp = Some(Otp::parse("JBSWY3DPEHPK3PXP").unwrap());
+
+        let report = audit(&entries, &BTreeMap::new(), 365, Utc::now());
+
+        assert!(report.reused.is_empty());
+        assert_eq!(report.weak, vec![(0, 0)]);
+        assert_eq!(report.without_otp, vec![0]);
+    }
+
+    #[test]
+    fn test_breached() {
+        let entries = vec![
+            entry("Mail", "john", "h7#Kq9!zP2@wLm4x"),
+            entry("Bank", "john", "Vb3$nT8&kR5*yH1q"),
+        ];
+        let breached = BTreeMap::from([(1, 42), (5, 3)]);
+
+        let report = audit(&entries, &breached, 365, Utc::now());
+
+        assert_eq!(report.breached, vec![(1, 42)]);
+    }
+}
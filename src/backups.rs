@@ -0,0 +1,285 @@
+use chrono::{Local, NaiveDateTime};
+use console::Term;
+use dialoguer::{theme::ColorfulTheme, Confirm, Select};
+use std::cmp::Reverse;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::{backup_retention, vault_path, vault_sibling_path};
+use crate::entry::Entry;
+use crate::languages::get_translation;
+use crate::utils::{read_vault_file, save_entries, unlock_vault_file};
+
+const SNAPSHOT_EXTENSION: &str = "snapshot";
+// Sorting names in this format sorts snapshots chronologically.
+const SNAPSHOT_NAME_FORMAT: &str = "%Y-%m-%d_%H-%M-%S%.3f";
+
+pub struct Snapshot {
+    pub name: String,
+    pub path: PathBuf,
+    pub taken_at: NaiveDateTime,
+}
+
+impl Snapshot {
+    pub fn read_entries(&self, password: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+        Ok(read_vault_file(&self.path, password)?.0)
+    }
+}
+
+/// What restoring a snapshot would change in the current vault.
+#[derive(Debug, Default, PartialEq)]
+pub struct VaultDiff {
+    pub added: Vec<Entry>,
+    pub removed: Vec<Entry>,
+    pub changed: Vec<Entry>,
+}
+
+impl VaultDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    pub fn display(&self) {
+        if self.is_empty() {
+            println!("\n  {}", get_translation("backup_identical"));
+            return;
+        }
+
+        for (title, symbol, entries) in [
+            ("diff_added", "+", &self.added),
+            ("diff_removed", "-", &self.removed),
+            ("diff_changed", "~", &self.changed),
+        ] {
+            if entries.is_empty() {
+                continue;
+            }
+
+            println!("\n  {}", get_translation(title));
+            for entry in entries {
+                println!("    {} {} ({})", symbol, entry.application, entry.username);
+            }
+        }
+    }
+}
+
+pub fn snapshots_directory() -> PathBuf {
+    vault_sibling_path(".snapshots")
+}
+
+/// Copies the encrypted vault into the snapshots directory and drops the oldest
+/// snapshots beyond the configured retention.
+pub fn take_snapshot() -> Result<(), Box<dyn Error>> {
+    let retention = backup_retention();
+
+    if retention == 0 {
+        return Ok(());
+    }
+
+    let directory = snapshots_directory();
+    fs::create_dir_all(&directory)?;
+
+    let name = Local::now().format(SNAPSHOT_NAME_FORMAT);
+    fs::copy(
+        vault_path(),
+        directory.join(format!("{}.{}", name, SNAPSHOT_EXTENSION)),
+    )?;
+
+    for snapshot in list_snapshots()?.iter().skip(retention) {
+        fs::remove_file(&snapshot.path)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the snapshots of the vault, most recent first.
+pub fn list_snapshots() -> Result<Vec<Snapshot>, Box<dyn Error>> {
+    let mut snapshots = Vec::new();
+
+    let dir_entries = match fs::read_dir(snapshots_directory()) {
+        Ok(dir_entries) => dir_entries,
+        Err(_) => return Ok(snapshots),
+    };
+
+    for dir_entry in dir_entries {
+        let path = dir_entry?.path();
+
+        if path.extension().and_then(OsStr::to_str) != Some(SNAPSHOT_EXTENSION) {
+            continue;
+        }
+
+        if let Some(name) = path.file_stem().and_then(OsStr::to_str) {
+            if let Ok(taken_at) = NaiveDateTime::parse_from_str(name, SNAPSHOT_NAME_FORMAT) {
+                snapshots.push(Snapshot {
+                    name: name.to_string(),
+                    path: path.clone(),
+                    taken_at,
+                });
+            }
+        }
+    }
+
+    snapshots.sort_by_key(|snapshot| Reverse(snapshot.taken_at));
+
+    Ok(snapshots)
+}
+
+pub fn diff_entries(current: &[Entry], target: &[Entry]) -> VaultDiff {
+    let mut diff = VaultDiff::default();
+
+    for entry in target {
+        match current.iter().find(|e| e.is_same_account(entry)) {
+            Some(existing) if !existing.has_same_content(entry) => diff.changed.push(entry.clone()),
+            Some(_) => {}
+            None => diff.added.push(entry.clone()),
+        }
+    }
+
+    for entry in current {
+        if !target.iter().any(|e| e.is_same_account(entry)) {
+            diff.removed.push(entry.clone());
+        }
+    }
+
+    diff
+}
+
+pub fn describe_snapshot(snapshot: &Snapshot, password: &str) -> String {
+    match snapshot.read_entries(password) {
+        Ok(entries) => format!("{} {}", entries.len(), get_translation("entries")),
+        Err(_) => get_translation("backup_locked"),
+    }
+}
+
+// Snapshots taken before the master password was changed need their own password.
+pub fn unlock_snapshot(snapshot: &Snapshot, password: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+    unlock_vault_file(
+        &snapshot.path,
+        password,
+        &get_translation("enter_backup_password"),
+    )
+}
+
+pub fn restore_backup(entries: &mut Vec<Entry>, password: &str) -> Result<(), Box<dyn Error>> {
+    let snapshots = list_snapshots()?;
+
+    if snapshots.is_empty() {
+        println!("\n  {}", get_translation("no_backup"));
+        Term::stderr().read_key()?;
+        return Ok(());
+    }
+
+    println!("\n  {}", get_translation("select_backup"));
+
+    let items: Vec<String> = snapshots
+        .iter()
+        .map(|snapshot| {
+            format!(
+                "{}  ·  {}",
+                snapshot.taken_at.format("%Y-%m-%d %H:%M:%S"),
+                describe_snapshot(snapshot, password)
+            )
+        })
+        .collect();
+
+    let snapshot = match Select::with_theme(&ColorfulTheme::default())
+        .default(0)
+        .items(&items)
+        .interact_opt()?
+    {
+        Some(selection) => &snapshots[selection],
+        None => return Ok(()),
+    };
+
+    let snapshot_entries = match unlock_snapshot(snapshot, password) {
+        Ok(snapshot_entries) => snapshot_entries,
+        Err(_) => {
+            println!("\n  {}", get_translation("invalid_password"));
+            Term::stderr().read_key()?;
+            return Ok(());
+        }
+    };
+
+    let diff = diff_entries(entries, &snapshot_entries);
+    diff.display();
+    println!();
+
+    if diff.is_empty() {
+        Term::stderr().read_key()?;
+        return Ok(());
+    }
+
+    if Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("  {}", get_translation("confirm_restore")))
+        .default(false)
+        .interact()?
+    {
+        *entries = snapshot_entries;
+        save_entries(entries, password)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn entry(application: &str, username: &str, password: &str) -> Entry {
+        let account = format!("{}\n{}", application, username);
+
+        Entry {
+            id: Uuid::new_v5(&Uuid::NAMESPACE_OID, account.as_bytes()),
+            password_changed_at: None,
+            created_at: None,
+            modified_at: None,
+            ..Entry::new(
+                application.to_string(),
+                username.to_string(),
+                password.to_string(),
+            )
+        }
+    }
+
+    #[test]
+    fn test_diff_entries() {
+        let current = vec![
+            entry("Mail", "john", "secret"),
+            entry("Bank", "john", "1234"),
+            entry("Forum", "johnny", "pass"),
+        ];
+        let target = vec![
+            entry("Mail", "john", "secret"),
+            entry("Bank", "john", "5678"),
+            entry("Shop", "john", "shop"),
+        ];
+
+        assert_eq!(
+            diff_entries(&current, &target),
+            VaultDiff {
+                added: vec![entry("Shop", "john", "shop")],
+                removed: vec![entry("Forum", "johnny", "pass")],
+                changed: vec![entry("Bank", "john", "5678")],
+            }
+        );
+    }
+
+    #[test]
+    fn test_diff_ignores_usage() {
+        let entries = vec![entry("Mail", "john", "secret")];
+        let mut used = entries.clone();
+        used[0].last_used_at = Some(Utc::now());
+
+        assert!(diff_entries(&entries, &used).is_empty());
+    }
+
+    #[test]
+    fn test_diff_identical_vaults() {
+        let entries = vec![entry("Mail", "john", "secret")];
+
+        assert!(diff_entries(&entries, &entries).is_empty());
+    }
+}
@@ -0,0 +1,183 @@
+use data_encoding::HEXUPPER;
+use sha1::{Digest, Sha1};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::audit::has_password;
+use crate::config::CONFIG;
+use crate::entry::Entry;
+
+/// Hash of a password as listed by Have I Been Pwned.
+pub fn password_hash(password: &str) -> String {
+    HEXUPPER.encode(&Sha1::digest(password.as_bytes()))
+}
+
+/// Looks for `key` in a file of `KEY:COUNT` lines sorted by key, without reading it
+/// all: the downloaded lists weigh tens of gigabytes. Returns how many times the
+/// password appeared in breaches.
+pub fn search_sorted_file<R: BufRead + Seek>(file: &mut R, key: &str) -> io::Result<Option<u64>> {
+    let mut low = 0;
+    let mut high = file.seek(SeekFrom::End(0))?;
+    let mut line = String::new();
+
+    // The line we look for, if any, starts between `low` and `high`.
+    while low < high {
+        let middle = low + (high - low) / 2;
+
+        // First line starting at `middle` or after it.
+        let mut start = middle;
+        if middle > 0 {
+            file.seek(SeekFrom::Start(middle - 1))?;
+            line.clear();
+            start = middle - 1 + file.read_line(&mut line)? as u64;
+        } else {
+            file.seek(SeekFrom::Start(0))?;
+        }
+        if start >= high {
+            high = middle;
+            continue;
+        }
+
+        line.clear();
+        let length = file.read_line(&mut line)? as u64;
+        let (hash, count) = line
+            .trim_end()
+            .split_once(':')
+            .unwrap_or((line.trim_end(), ""));
+
+        match hash.to_ascii_uppercase().as_str().cmp(key) {
+            Ordering::Equal => return Ok(Some(count.trim().parse().unwrap_or(1))),
+            Ordering::Less => low = start + length,
+            Ordering::Greater => high = middle,
+        }
+    }
+
+    Ok(None)
+}
+
+/// How many times a password appeared in breaches, according to the list at `path`.
+///
+/// It is either the whole list in one file, or a directory of range files named after
+/// the first five characters of the hashes they hold, as written by the official
+/// downloader.
+pub fn breach_count(path: &Path, password: &str) -> io::Result<Option<u64>> {
+    let hash = password_hash(password);
+
+    if path.is_dir() {
+        let (prefix, suffix) = hash.split_at(5);
+        let range = path.join(prefix);
+        let range = if range.exists() {
+            range
+        } else {
+            path.join(format!("{}.txt", prefix))
+        };
+
+        return match File::open(range) {
+            Ok(file) => search_sorted_file(&mut BufReader::new(file), suffix),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        };
+    }
+
+    search_sorted_file(&mut BufReader::new(File::open(path)?), &hash)
+}
+
+/// Entries whose password appeared in breaches, with how many times, when a list of
+/// breached passwords is set in the config file. Entries in the trash are left out.
+pub fn breached_entries(entries: &[Entry]) -> io::Result<BTreeMap<usize, u64>> {
+    let path = match CONFIG.lock().unwrap().breached_passwords_path.clone() {
+        Some(path) => path,
+        None => return Ok(BTreeMap::new()),
+    };
+
+    let mut breached = BTreeMap::new();
+    for (index, entry) in entries.iter().enumerate() {
+        if entry.is_in_trash() || !has_password(entry) {
+            continue;
+        }
+        if let Some(count) = breach_count(&path, &entry.password)? {
+            breached.insert(index, count);
+        }
+    }
+
+    Ok(breached)
+}
+
+/// Entries whose password appeared in breaches, or why the list cannot be read.
+pub type BreachCheck = Result<BTreeMap<usize, u64>, String>;
+
+/// Checks the entries once, for the interactive interface, which shows the result until
+/// they change.
+pub fn check_breaches(entries: &[Entry]) -> BreachCheck {
+    breached_entries(entries).map_err(|error| error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    // Hashes of "123456", "password", "letmein" and "hunter2", with a few others.
+    const FIXTURE: &str = "\
+        0000000A0E3B9F25FF41DE4B5AC238C2D545C7A8:15\r\n\
+        5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8:9545824\r\n\
+        7C4A8D09CA3762AF61E59520943DC26494F8941B:37359195\r\n\
+        B7A875FC1EA228B9061041B7CEC4BD3C52AB3CE3:511323\r\n\
+        F3BBBD66A63D4BF1747940578EC3D0103530E21D:24230\r\n\
+        FFFFFFF8A0382AA9C8D9536EFBA77F261815334D:3\r\n";
+
+    fn search(key: &str) -> Option<u64> {
+        search_sorted_file(&mut Cursor::new(FIXTURE), key).unwrap()
+    }
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir().join(format!("neopass-breaches-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_search_sorted_file() {
+        assert_eq!(search(&password_hash("password")), Some(9545824));
+        assert_eq!(search(&password_hash("hunter2")), Some(24230));
+        assert_eq!(search("0000000A0E3B9F25FF41DE4B5AC238C2D545C7A8"), Some(15));
+        assert_eq!(search("FFFFFFF8A0382AA9C8D9536EFBA77F261815334D"), Some(3));
+        assert_eq!(search(&password_hash("h7#Kq9!zP2@wLm4x")), None);
+        assert_eq!(search("0000000000000000000000000000000000000000"), None);
+        assert_eq!(search("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"), None);
+        assert_eq!(
+            search_sorted_file(&mut Cursor::new(""), "5BAA6").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_breach_count() {
+        let file = temp_path();
+        fs::write(&file, FIXTURE).unwrap();
+
+        assert_eq!(breach_count(&file, "letmein").unwrap(), Some(511323));
+        assert_eq!(breach_count(&file, "h7#Kq9!zP2@wLm4x").unwrap(), None);
+
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_range_directory() {
+        let directory = temp_path();
+        fs::create_dir(&directory).unwrap();
+        fs::write(
+            directory.join("5BAA6.txt"),
+            "1E4C9B93F3F0682250B6CF8331B7EE68FD8:9545824\r\n1E4CA000000000000000000000000000000:2\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(breach_count(&directory, "password").unwrap(), Some(9545824));
+        assert_eq!(breach_count(&directory, "123456").unwrap(), None);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,1061 @@
+use chrono::Utc;
+use clap::{Args, Parser, Subcommand};
+use dialoguer::{theme::ColorfulTheme, Confirm, Password};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::attachments::{
+    add_attachment, describe_attachment, extract_attachment, remove_attachment,
+};
+use crate::audit::{audit, print_report};
+use crate::backups::{describe_snapshot, diff_entries, list_snapshots, unlock_snapshot};
+use crate::breaches::breached_entries;
+use crate::config::{
+    password_history_retention, password_policy, vault_path, CONFIG, VAULT_ENV_VARIABLE,
+};
+use crate::derived::{derives_from_former_names, keep_derivation_inputs};
+use crate::entry::{details_table, Entry};
+use crate::inject::{
+    check_output_permissions, placeholders, render, write_private_file, Reference,
+};
+use crate::kinds::EntryKind;
+use crate::languages::get_translation;
+use crate::lock::{check_vault_is_writable, VaultLockError};
+use crate::merge::merge_vault_file;
+use crate::output::{entry_json, field_value, EntryJson};
+use crate::passphrase::generate_passphrase;
+use crate::run::{parse_env_reference, run_with_secrets, EnvReference};
+use crate::trash::{self, move_to_trash};
+use crate::utils::{
+    copy_one_time_code, decrypt_file, mark_entry_as_used, read_vault_file,
+    set_password_in_clipboard, write_entries_in_file, InvalidPasswordError,
+};
+use crate::vaults::{current_vault_label, register_vault, set_default_vault, unregister_vault};
+
+/// A simple and secure command-line password manager.
+///
+/// Without a command, NeoPass opens its interactive interface.
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Name of a registered vault, or path of the vault to open.
+    #[arg(long, global = true, env = VAULT_ENV_VARIABLE)]
+    pub vault: Option<String>,
+
+    /// Read the master password from the first line of the standard input.
+    #[arg(long, global = true)]
+    pub password_stdin: bool,
+
+    /// Read the master password from this file descriptor, 3 or above.
+    #[arg(
+        long,
+        global = true,
+        value_name = "FD",
+        value_parser = clap::value_parser!(i32).range(3..),
+        conflicts_with = "password_stdin"
+    )]
+    pub password_fd: Option<i32>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Seconds `neopass copy` keeps the clipboard filled on X11.
+const CLIPBOARD_DELAY: u64 = 45;
+
+impl Cli {
+    pub fn password_source(&self) -> PasswordSource {
+        match (self.password_stdin, self.password_fd) {
+            (true, _) => PasswordSource::Stdin,
+            (false, Some(fd)) => PasswordSource::Fd(fd),
+            (false, None) => PasswordSource::Prompt,
+        }
+    }
+}
+
+/// Where commands read the master password from.
+#[derive(Clone, Copy)]
+pub enum PasswordSource {
+    Prompt,
+    Stdin,
+    Fd(i32),
+}
+
+/// Errors of commands that scripts may want to tell apart, through the exit code.
+#[derive(Debug)]
+pub enum CliError {
+    NotFound(String),
+    Ambiguous(String),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::NotFound(message) | CliError::Ambiguous(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+impl Error for CliError {}
+
+/// Exit code of a failed command: 3 when the entry is not found, 4 when several
+/// entries match, 5 when the master password is wrong, 6 when the vault cannot be
+/// written and 1 otherwise. Invalid arguments exit with 2.
+pub fn exit_code(error: &(dyn Error + 'static)) -> i32 {
+    if let Some(error) = error.downcast_ref::<CliError>() {
+        return match error {
+            CliError::NotFound(_) => 3,
+            CliError::Ambiguous(_) => 4,
+        };
+    }
+    if error.is::<InvalidPasswordError>() {
+        return 5;
+    }
+    if error.is::<VaultLockError>() {
+        return 6;
+    }
+    1
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// List the snapshots of the vault, or restore one of them.
+    Restore {
+        /// Name of the snapshot to restore, as listed by `neopass restore`.
+        snapshot: Option<String>,
+
+        /// Restore without asking for confirmation.
+        #[arg(long)]
+        yes: bool,
+    },
+
+    /// Merge another copy of the vault into this one, e.g. a copy synced on another device.
+    Merge {
+        /// The other copy of the vault.
+        other: PathBuf,
+
+        /// The last version both copies had in common, such as one of their snapshots.
+        #[arg(long)]
+        base: Option<PathBuf>,
+    },
+
+    /// Manage the registry of named vaults.
+    #[command(subcommand)]
+    Vaults(VaultsCommand),
+
+    /// Manage the files attached to entries.
+    #[command(subcommand)]
+    Attachments(AttachmentsCommand),
+
+    /// List the entries of the vault: their name, username and identifier.
+    List {
+        /// Only list the entries with this tag.
+        #[arg(long)]
+        tag: Option<String>,
+
+        #[command(flatten)]
+        json: JsonArgs,
+    },
+
+    /// Show an entry, with its secrets masked.
+    Get {
+        entry: String,
+
+        #[command(flatten)]
+        json: JsonArgs,
+
+        /// Print only this field, raw, secrets included.
+        #[arg(long, conflicts_with = "json")]
+        field: Option<String>,
+    },
+
+    /// Add an entry. Its password is asked for unless generated or read from the
+    /// standard input.
+    Add {
+        name: String,
+
+        /// Kind of entry, such as `login`, `card` or `api_token`.
+        #[arg(long, default_value = "login", value_parser = parse_kind)]
+        kind: EntryKind,
+
+        #[arg(long, default_value = "")]
+        username: String,
+
+        #[command(flatten)]
+        details: DetailsArgs,
+
+        #[command(flatten)]
+        secret: SecretArgs,
+    },
+
+    /// Change an entry. Only the given values change; URLs and tags are replaced.
+    Edit {
+        entry: String,
+
+        /// New name of the entry.
+        #[arg(long)]
+        name: Option<String>,
+
+        #[arg(long)]
+        username: Option<String>,
+
+        #[command(flatten)]
+        details: DetailsArgs,
+
+        #[command(flatten)]
+        secret: SecretArgs,
+    },
+
+    /// Move an entry to the trash.
+    Rm {
+        entry: String,
+
+        /// Delete the entry for good instead, with its attachments.
+        #[arg(long)]
+        purge: bool,
+    },
+
+    /// Print a new password following the default rules, or a passphrase.
+    Generate {
+        #[arg(long)]
+        length: Option<usize>,
+
+        /// Leave symbols out.
+        #[arg(long)]
+        no_symbols: bool,
+
+        #[arg(long)]
+        passphrase: bool,
+
+        /// Number of words of the passphrase.
+        #[arg(long, requires = "passphrase")]
+        words: Option<usize>,
+    },
+
+    /// Copy the password of an entry to the clipboard.
+    Copy {
+        entry: String,
+
+        /// Copy its one-time code instead.
+        #[arg(long)]
+        otp: bool,
+    },
+
+    /// Run a command with secrets of the vault in its environment. They are masked in
+    /// what it prints.
+    Run {
+        /// Variable to set, as `VAR=entry:"<entry>".<field>`. Repeat it for several
+        /// variables.
+        #[arg(long = "env", value_name = "VAR=REFERENCE", required = true, value_parser = parse_env_reference)]
+        variables: Vec<EnvReference>,
+
+        /// The command and its arguments, after `--`.
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+
+    /// Fill a template with secrets, replacing every
+    /// `{{ neopass://<vault>/<entry>/<field> }}` with the field of the entry.
+    Inject {
+        /// The template.
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// File to write, instead of the standard output. It is only readable by you
+        /// when created, and is not overwritten when every user can read it.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// List the references that cannot be resolved, without writing anything.
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Report reused, weak and old passwords, duplicated entries and missing 2FA.
+    Audit {
+        /// Report passwords older than this many days. Defaults to `password_rotation_days`.
+        #[arg(long)]
+        days: Option<u64>,
+    },
+}
+
+#[derive(Args)]
+pub struct JsonArgs {
+    /// Print the entries as JSON, in the format documented in the README.
+    #[arg(long)]
+    json: bool,
+
+    /// Include passwords and other concealed values in the JSON.
+    #[arg(long, requires = "json")]
+    reveal: bool,
+}
+
+#[derive(Args)]
+pub struct DetailsArgs {
+    /// URL of the entry. Repeat it for several URLs.
+    #[arg(long = "url")]
+    urls: Vec<String>,
+
+    /// Tag of the entry. Repeat it for several tags.
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+
+    #[arg(long)]
+    notes: Option<String>,
+}
+
+#[derive(Args)]
+pub struct SecretArgs {
+    /// Generate the password following the default rules.
+    #[arg(long, conflicts_with = "secret_stdin")]
+    generate: bool,
+
+    /// Read the password, or other secret, from the standard input. With
+    /// `--password-stdin`, it is the line after the master password.
+    #[arg(long)]
+    secret_stdin: bool,
+}
+
+#[derive(Subcommand)]
+pub enum VaultsCommand {
+    /// List the registered vaults.
+    List,
+
+    /// Register a vault. It is created the first time it is opened.
+    Add {
+        name: String,
+
+        /// Where to store the vault. Defaults to the data directory.
+        #[arg(long)]
+        path: Option<PathBuf>,
+    },
+
+    /// Remove a vault from the registry, without deleting its file.
+    Remove { name: String },
+
+    /// Open this vault when `--vault` is not given.
+    Default { name: String },
+}
+
+/// Entries are designated by their name or their identifier.
+#[derive(Subcommand)]
+pub enum AttachmentsCommand {
+    /// List the files attached to an entry.
+    List { entry: String },
+
+    /// Attach a file to an entry.
+    Add { entry: String, file: PathBuf },
+
+    /// Write an attached file to disk.
+    Extract {
+        entry: String,
+        name: String,
+
+        /// File or directory to write it to.
+        #[arg(long, default_value = ".")]
+        output: PathBuf,
+    },
+
+    /// Delete a file attached to an entry.
+    Delete { entry: String, name: String },
+}
+
+pub fn run_command(command: Command, source: PasswordSource) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Restore { snapshot, yes } => restore(snapshot, yes, source),
+        Command::Merge { other, base } => merge(&other, base.as_deref(), source),
+        Command::Vaults(VaultsCommand::List) => {
+            list_vaults();
+            Ok(())
+        }
+        Command::Vaults(VaultsCommand::Add { name, path }) => {
+            let path = register_vault(&name, path)?;
+            println!("{}  ·  {}", name, path.display());
+            Ok(())
+        }
+        Command::Vaults(VaultsCommand::Remove { name }) => unregister_vault(&name),
+        Command::Vaults(VaultsCommand::Default { name }) => set_default_vault(&name),
+        Command::Attachments(command) => attachments(command, source),
+        Command::List { tag, json } => {
+            let (entries, password) = unlock_vault(source)?;
+            let listed: Vec<&Entry> = entries
+                .iter()
+                .filter(|e| !e.is_in_trash())
+                .filter(|e| tag.as_ref().is_none_or(|tag| e.tags.contains(tag)))
+                .collect();
+
+            if json.json {
+                let listed = listed
+                    .into_iter()
+                    .map(|entry| entry_json(entry, &password, json.reveal))
+                    .collect::<Result<Vec<EntryJson>, Box<dyn Error>>>()?;
+                println!("{}", serde_json::to_string_pretty(&listed)?);
+            } else {
+                for entry in listed {
+                    println!("{}\t{}\t{}", entry.application, entry.username, entry.id);
+                }
+            }
+            Ok(())
+        }
+        Command::Get { entry, json, field } => {
+            let (entries, password) = unlock_vault(source)?;
+            let entry = &entries[find_entry(&entries, &entry)?];
+
+            if let Some(field) = field {
+                match field_value(entry, &field, &password)? {
+                    Some(value) => println!("{}", value),
+                    None => {
+                        return Err(Box::new(CliError::NotFound(format!(
+                            "No field named {} in {}",
+                            field, entry.application
+                        ))))
+                    }
+                }
+            } else if json.json {
+                let entry = entry_json(entry, &password, json.reveal)?;
+                println!("{}", serde_json::to_string_pretty(&entry)?);
+            } else {
+                println!("{}", details_table(entry));
+            }
+            Ok(())
+        }
+        Command::Add {
+            name,
+            kind,
+            username,
+            details,
+            secret,
+        } => add(name, kind, username, details, secret, source),
+        Command::Edit {
+            entry,
+            name,
+            username,
+            details,
+            secret,
+        } => edit(&entry, name, username, details, secret, source),
+        Command::Rm { entry, purge } => {
+            let (mut entries, password) = unlock_vault(source)?;
+            let index = find_entry(&entries, &entry)?;
+            check_vault_is_writable(&vault_path())?;
+            if purge {
+                trash::purge(&mut entries, index);
+            } else {
+                move_to_trash(&mut entries, index);
+            }
+            write_entries_in_file(&entries, &password)
+        }
+        Command::Generate {
+            length,
+            no_symbols,
+            passphrase,
+            words,
+        } => {
+            if passphrase {
+                let mut options = CONFIG.lock().unwrap().passphrase.clone();
+                options.words = words.unwrap_or(options.words);
+                println!("{}", generate_passphrase(&options)?.value);
+            } else {
+                let mut policy = password_policy();
+                policy.length = length.unwrap_or(policy.length);
+                policy.symbols &= !no_symbols;
+                println!("{}", policy.generate()?);
+            }
+            Ok(())
+        }
+        Command::Copy { entry, otp } => copy(&entry, otp, source),
+        Command::Run { variables, command } => {
+            let (entries, password) = unlock_vault(source)?;
+
+            let mut values = Vec::new();
+            for reference in variables {
+                let entry = &entries[find_entry(&entries, &reference.entry)?];
+                match field_value(entry, &reference.field, &password)? {
+                    Some(value) => values.push((reference.variable, value)),
+                    None => {
+                        return Err(Box::new(CliError::NotFound(format!(
+                            "No field named {} in {}",
+                            reference.field, entry.application
+                        ))))
+                    }
+                }
+            }
+
+            // The command decides how NeoPass exits.
+            let code = run_with_secrets(&command, &values)?;
+            process::exit(code)
+        }
+        Command::Inject {
+            input,
+            output,
+            dry_run,
+        } => inject(&input, output.as_deref(), dry_run, source),
+        Command::Audit { days } => {
+            let (entries, _) = unlock_vault(source)?;
+            let days = days.unwrap_or_else(|| CONFIG.lock().unwrap().password_rotation_days);
+            let report = audit(&entries, &breached_entries(&entries)?, days, Utc::now());
+            print_report(&report, &entries, days);
+            Ok(())
+        }
+    }
+}
+
+fn find_entry(entries: &[Entry], query: &str) -> Result<usize, Box<dyn Error>> {
+    // Entries in the trash are left out.
+    if let Some(index) = entries
+        .iter()
+        .position(|e| !e.is_in_trash() && e.id.to_string() == query)
+    {
+        return Ok(index);
+    }
+
+    let matches: Vec<usize> = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| !e.is_in_trash() && e.application.eq_ignore_ascii_case(query))
+        .map(|(index, _)| index)
+        .collect();
+
+    match matches[..] {
+        [index] => Ok(index),
+        [] => Err(Box::new(CliError::NotFound(format!(
+            "No entry named {}",
+            query
+        )))),
+        _ => Err(Box::new(CliError::Ambiguous(format!(
+            "Several entries are named {}, use one of their identifiers instead: {}",
+            query,
+            matches
+                .iter()
+                .map(|index| entries[*index].id.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )))),
+    }
+}
+
+fn find_attachment(entry: &Entry, name: &str) -> Result<usize, Box<dyn Error>> {
+    match entry.attachments.iter().position(|a| a.name == name) {
+        Some(index) => Ok(index),
+        None => Err(Box::new(CliError::NotFound(format!(
+            "No file named {} is attached to {}",
+            name, entry.application
+        )))),
+    }
+}
+
+fn attachments(command: AttachmentsCommand, source: PasswordSource) -> Result<(), Box<dyn Error>> {
+    let (mut entries, password) = unlock_vault(source)?;
+
+    match command {
+        AttachmentsCommand::List { entry } => {
+            let index = find_entry(&entries, &entry)?;
+            for attachment in &entries[index].attachments {
+                println!("{}", describe_attachment(attachment));
+            }
+            Ok(())
+        }
+        AttachmentsCommand::Add { entry, file } => {
+            let index = find_entry(&entries, &entry)?;
+            check_vault_is_writable(&vault_path())?;
+            add_attachment(&mut entries[index], &file)?;
+            write_entries_in_file(&entries, &password)
+        }
+        AttachmentsCommand::Extract {
+            entry,
+            name,
+            output,
+        } => {
+            let index = find_entry(&entries, &entry)?;
+            let attachment = find_attachment(&entries[index], &name)?;
+            let path = extract_attachment(&entries[index].attachments[attachment], &output)?;
+            println!("{}", path.display());
+            Ok(())
+        }
+        AttachmentsCommand::Delete { entry, name } => {
+            let index = find_entry(&entries, &entry)?;
+            let attachment = find_attachment(&entries[index], &name)?;
+            check_vault_is_writable(&vault_path())?;
+            remove_attachment(&mut entries[index], attachment);
+            write_entries_in_file(&entries, &password)
+        }
+    }
+}
+
+fn list_vaults() {
+    let config = CONFIG.lock().unwrap();
+
+    for (name, settings) in &config.vaults {
+        let marker = if config.default_vault.as_ref() == Some(name) {
+            "*"
+        } else {
+            " "
+        };
+        println!("{} {}  ·  {}", marker, name, settings.path.display());
+    }
+}
+
+// Reads one line, without its line break.
+fn read_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+// Opened again through `/dev/fd`, which fails when the descriptor is not open instead
+// of taking over whatever it refers to.
+#[cfg(unix)]
+fn open_fd(fd: i32) -> io::Result<File> {
+    File::open(format!("/dev/fd/{}", fd)).map_err(|error| match error.kind() {
+        io::ErrorKind::NotFound => io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("File descriptor {} is not open", fd),
+        ),
+        _ => error,
+    })
+}
+
+#[cfg(not(unix))]
+fn open_fd(_fd: i32) -> io::Result<File> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--password-fd is only supported on Unix",
+    ))
+}
+
+// Entries of an unlocked vault, with its master password.
+type UnlockedVault = (Vec<Entry>, String);
+
+/// Reads master passwords. Outside of the terminal, each one is a line.
+struct PasswordReader {
+    source: PasswordSource,
+    fd: Option<BufReader<File>>,
+}
+
+impl PasswordReader {
+    fn new(source: PasswordSource) -> Self {
+        PasswordReader { source, fd: None }
+    }
+
+    fn read(&mut self, prompt: &str) -> Result<String, Box<dyn Error>> {
+        Ok(match self.source {
+            PasswordSource::Prompt => Password::with_theme(&ColorfulTheme::default())
+                .with_prompt(prompt)
+                .interact()?,
+            PasswordSource::Stdin => read_line(&mut io::stdin().lock())?,
+            PasswordSource::Fd(fd) => {
+                // Kept open, for the next vault.
+                let reader = match &mut self.fd {
+                    Some(reader) => reader,
+                    None => self.fd.insert(BufReader::new(open_fd(fd)?)),
+                };
+                read_line(reader)?
+            }
+        })
+    }
+}
+
+fn unlock_vault(source: PasswordSource) -> Result<UnlockedVault, Box<dyn Error>> {
+    if !vault_path().exists() {
+        return Err(Box::new(CliError::NotFound(format!(
+            "No vault found at {}",
+            vault_path().display()
+        ))));
+    }
+
+    let password = PasswordReader::new(source).read(&get_translation("enter_password"))?;
+    let entries = decrypt_file(&password)?;
+
+    Ok((entries, password))
+}
+
+// The new secret of an entry, if one is given.
+fn read_secret(secret: &SecretArgs) -> Result<Option<String>, Box<dyn Error>> {
+    if secret.generate {
+        return Ok(Some(password_policy().generate()?));
+    }
+    if secret.secret_stdin {
+        return Ok(Some(read_line(&mut io::stdin().lock())?));
+    }
+    Ok(None)
+}
+
+fn set_details(entry: &mut Entry, details: DetailsArgs) {
+    if !details.urls.is_empty() {
+        entry.urls = details.urls;
+    }
+    if !details.tags.is_empty() {
+        entry.tags = details.tags.into_iter().collect();
+    }
+    if let Some(notes) = details.notes {
+        entry.notes = notes;
+    }
+}
+
+fn parse_kind(kind: &str) -> Result<EntryKind, String> {
+    serde_json::from_value(serde_json::Value::String(kind.to_string())).map_err(|_| {
+        format!(
+            "unknown kind, expected one of: {}",
+            EntryKind::ALL
+                .iter()
+                .map(|kind| serde_json::to_value(kind)
+                    .unwrap()
+                    .as_str()
+                    .unwrap()
+                    .to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+    })
+}
+
+fn add(
+    name: String,
+    kind: EntryKind,
+    username: String,
+    details: DetailsArgs,
+    secret: SecretArgs,
+    source: PasswordSource,
+) -> Result<(), Box<dyn Error>> {
+    let (mut entries, password) = unlock_vault(source)?;
+    check_vault_is_writable(&vault_path())?;
+
+    let mut entry = Entry::new(name, username, String::new());
+    entry.kind = kind;
+    set_details(&mut entry, details);
+
+    if let Some(label) = kind.schema().secret {
+        entry.password = match read_secret(&secret)? {
+            Some(secret) => secret,
+            None => Password::with_theme(&ColorfulTheme::default())
+                .with_prompt(get_translation(label))
+                .interact()?,
+        };
+    }
+
+    println!("{}", entry.id);
+    entries.push(entry);
+    write_entries_in_file(&entries, &password)
+}
+
+fn edit(
+    query: &str,
+    name: Option<String>,
+    username: Option<String>,
+    details: DetailsArgs,
+    secret: SecretArgs,
+    source: PasswordSource,
+) -> Result<(), Box<dyn Error>> {
+    let (mut entries, password) = unlock_vault(source)?;
+    let index = find_entry(&entries, query)?;
+    check_vault_is_writable(&vault_path())?;
+
+    let entry = &mut entries[index];
+    keep_derivation_inputs(entry);
+    let before = entry.clone();
+
+    if let Some(name) = name {
+        entry.application = name;
+    }
+    if let Some(username) = username {
+        entry.username = username;
+    }
+    set_details(entry, details);
+
+    if let Some(secret) = read_secret(&secret)? {
+        // A password given for a derived entry is stored from now on.
+        entry.derivation = None;
+        entry.replace_password(secret, password_history_retention());
+    }
+    if derives_from_former_names(entry) {
+        eprintln!(
+            "{} {}",
+            entry.application,
+            get_translation("still_derived_from_former_names")
+        );
+    }
+
+    if !entry.has_same_content(&before) {
+        entry.modified_at = Some(Utc::now());
+    }
+
+    write_entries_in_file(&entries, &password)
+}
+
+fn copy(query: &str, otp: bool, source: PasswordSource) -> Result<(), Box<dyn Error>> {
+    let (mut entries, password) = unlock_vault(source)?;
+    let index = find_entry(&entries, query)?;
+
+    if otp {
+        if entries[index].otp.is_none() {
+            return Err(Box::new(CliError::NotFound(get_translation(
+                "no_one_time_code",
+            ))));
+        }
+
+        let mut message = None;
+        if copy_one_time_code(&mut entries, index, &mut message)? {
+            write_entries_in_file(&entries, &password)?;
+        }
+        eprintln!("{}", message.unwrap_or_default());
+        keep_clipboard_content();
+        return Ok(());
+    }
+
+    set_password_in_clipboard(&entries, index, &password, &mut None)?;
+    eprintln!("{}", get_translation("password_copied").trim_end());
+    mark_entry_as_used(&mut entries, index, &password)?;
+    keep_clipboard_content();
+
+    Ok(())
+}
+
+// On X11, which XWayland uses too, the clipboard is served by the program that filled
+// it and empties when it exits. NeoPass keeps running for CLIPBOARD_DELAY seconds so
+// the copy can be pasted.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn keep_clipboard_content() {
+    eprintln!(
+        "{} {}s",
+        get_translation("clipboard_kept_for"),
+        CLIPBOARD_DELAY
+    );
+    std::thread::sleep(std::time::Duration::from_secs(CLIPBOARD_DELAY));
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+fn keep_clipboard_content() {}
+
+// Entries of a vault named in a template, or why they cannot be read. An empty name
+// is the vault NeoPass opens.
+fn read_named_vault(
+    name: &str,
+    reader: &mut PasswordReader,
+) -> Result<Result<UnlockedVault, String>, Box<dyn Error>> {
+    let (label, path) = if name.is_empty() {
+        (current_vault_label(), vault_path())
+    } else {
+        match CONFIG.lock().unwrap().vaults.get(name) {
+            Some(settings) => (name.to_string(), settings.path.clone()),
+            None => return Ok(Err(format!("No vault named {}", name))),
+        }
+    };
+
+    if !path.exists() {
+        return Ok(Err(format!("No vault found at {}", path.display())));
+    }
+
+    let password = reader.read(&format!(
+        "{} ({})",
+        get_translation("enter_password"),
+        label
+    ))?;
+    let (entries, _) = read_vault_file(&path, &password)?;
+
+    Ok(Ok((entries, password)))
+}
+
+fn resolve_reference(
+    entries: &[Entry],
+    password: &str,
+    reference: &Reference,
+) -> Result<String, String> {
+    let entry = &entries[find_entry(entries, &reference.entry).map_err(|e| e.to_string())?];
+
+    match field_value(entry, &reference.field, password).map_err(|e| e.to_string())? {
+        Some(value) => Ok(value),
+        None => Err(format!(
+            "No field named {} in {}",
+            reference.field, entry.application
+        )),
+    }
+}
+
+fn inject(
+    input: &Path,
+    output: Option<&Path>,
+    dry_run: bool,
+    source: PasswordSource,
+) -> Result<(), Box<dyn Error>> {
+    let template = fs::read_to_string(input)?;
+    let placeholders = placeholders(&template);
+
+    // Before asking for any password.
+    if let (Some(output), false) = (output, dry_run) {
+        check_output_permissions(output)?;
+    }
+
+    let mut reader = PasswordReader::new(source);
+    let mut vaults = HashMap::new();
+    let mut values = Vec::new();
+    let mut unresolved = Vec::new();
+
+    // Vaults are unlocked in the order they appear in, which is the order of their
+    // passwords on the standard input.
+    for placeholder in &placeholders {
+        let value = match &placeholder.reference {
+            Some(reference) => {
+                if !vaults.contains_key(&reference.vault) {
+                    let vault = read_named_vault(&reference.vault, &mut reader)?;
+                    vaults.insert(reference.vault.clone(), vault);
+                }
+                match &vaults[&reference.vault] {
+                    Ok((entries, password)) => resolve_reference(entries, password, reference),
+                    Err(reason) => Err(reason.clone()),
+                }
+            }
+            None => Err("Expected neopass://<vault>/<entry>/<field>".to_string()),
+        };
+
+        match value {
+            Ok(value) => values.push(value),
+            Err(reason) => unresolved.push(format!(
+                "line {}: {{{{ {} }}}}: {}",
+                placeholder.line, placeholder.text, reason
+            )),
+        }
+    }
+
+    if dry_run {
+        println!(
+            "{} references, {} unresolved",
+            placeholders.len(),
+            unresolved.len()
+        );
+        for reference in &unresolved {
+            println!("  {}", reference);
+        }
+    }
+
+    if !unresolved.is_empty() {
+        let mut message = format!("{} references cannot be resolved", unresolved.len());
+        if !dry_run {
+            message.push_str(", nothing was written:");
+            for reference in &unresolved {
+                message.push_str(&format!("\n  {}", reference));
+            }
+        }
+        return Err(Box::new(CliError::NotFound(message)));
+    }
+
+    if dry_run {
+        return Ok(());
+    }
+
+    let content = render(&template, &placeholders, &values);
+    match output {
+        Some(output) => write_private_file(output, &content),
+        None => {
+            print!("{}", content);
+            Ok(())
+        }
+    }
+}
+
+fn merge(other: &Path, base: Option<&Path>, source: PasswordSource) -> Result<(), Box<dyn Error>> {
+    for path in [Some(other), base].into_iter().flatten() {
+        if !path.exists() {
+            return Err(format!("No vault found at {}", path.display()).into());
+        }
+    }
+
+    let (entries, password) = unlock_vault(source)?;
+
+    merge_vault_file(&entries, &password, other, base)
+}
+
+fn restore(name: Option<String>, yes: bool, source: PasswordSource) -> Result<(), Box<dyn Error>> {
+    let (entries, password) = unlock_vault(source)?;
+    let snapshots = list_snapshots()?;
+
+    let name = match name {
+        Some(name) => name,
+        None => {
+            for snapshot in &snapshots {
+                println!(
+                    "{}  ·  {}",
+                    snapshot.name,
+                    describe_snapshot(snapshot, &password)
+                );
+            }
+            return Ok(());
+        }
+    };
+
+    let snapshot = match snapshots.iter().find(|snapshot| snapshot.name == name) {
+        Some(snapshot) => snapshot,
+        None => {
+            return Err(Box::new(CliError::NotFound(format!(
+                "No snapshot named {}",
+                name
+            ))))
+        }
+    };
+
+    let snapshot_entries = unlock_snapshot(snapshot, &password)?;
+    let diff = diff_entries(&entries, &snapshot_entries);
+    diff.display();
+    println!();
+
+    if diff.is_empty() {
+        return Ok(());
+    }
+
+    if yes
+        || Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(get_translation("confirm_restore"))
+            .default(false)
+            .interact()?
+    {
+        write_entries_in_file(&snapshot_entries, &password)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(application: &str) -> Entry {
+        Entry::new(
+            application.to_string(),
+            "john".to_string(),
+            "h7#Kq9!zP2@wLm4x".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_find_entry_exit_codes() {
+        let entries = vec![entry("Mail"), entry("Bank"), entry("Bank")];
+
+        assert_eq!(find_entry(&entries, "Mail").unwrap(), 0);
+        assert_eq!(find_entry(&entries, &entries[2].id.to_string()).unwrap(), 2);
+        assert_eq!(
+            exit_code(find_entry(&entries, "Shop").unwrap_err().as_ref()),
+            3
+        );
+        assert_eq!(
+            exit_code(find_entry(&entries, "Bank").unwrap_err().as_ref()),
+            4
+        );
+        assert_eq!(exit_code(&InvalidPasswordError), 5);
+        assert_eq!(exit_code(&io::Error::other("Broken pipe")), 1);
+    }
+
+    #[test]
+    fn test_read_line() {
+        let mut input = io::Cursor::new("master password\r\nsecret\n");
+
+        assert_eq!(read_line(&mut input).unwrap(), "master password");
+        assert_eq!(read_line(&mut input).unwrap(), "secret");
+        assert_eq!(read_line(&mut input).unwrap(), "");
+    }
+}
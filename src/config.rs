@@ -1,59 +1,274 @@
-use crate::languages::{Language, Translations};
+use crate::generator::PasswordPolicy;
+use crate::languages::{get_translation, Language, Translations};
+use crate::lock::lock_vault;
+use crate::passphrase::PassphraseOptions;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     error::Error,
+    ffi::OsString,
     fs::{self, File},
     io::{Read, Write},
+    path::{Path, PathBuf},
     sync::Mutex,
 };
 
 #[derive(Serialize, Deserialize)]
-pub struct Config<'a> {
-    language_code: &'a str,
+pub struct Config {
+    #[serde(default = "default_language_code")]
+    pub language_code: String,
+    #[serde(default = "default_backup_retention")]
+    pub backup_retention: usize,
+    #[serde(default = "default_password_history_retention")]
+    pub password_history_retention: usize,
+    // Passwords older than this are reported by the security audit.
+    #[serde(default = "default_password_rotation_days")]
+    pub password_rotation_days: u64,
+    // Ask before moving an entry to the trash.
+    #[serde(default)]
+    pub confirm_deletions: bool,
+    #[serde(default)]
+    pub password_policy: PasswordPolicy,
+    #[serde(default)]
+    pub passphrase: PassphraseOptions,
+    // Passwords leaked in breaches, as downloaded from Have I Been Pwned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub breached_passwords_path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vault_path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_vault: Option<String>,
+    #[serde(default)]
+    pub vaults: BTreeMap<String, VaultSettings>,
 }
 
-pub const LOCAL_CONFIG_PATH: &str = "local_config.json";
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            language_code: default_language_code(),
+            backup_retention: default_backup_retention(),
+            password_history_retention: default_password_history_retention(),
+            password_rotation_days: default_password_rotation_days(),
+            confirm_deletions: false,
+            password_policy: PasswordPolicy::default(),
+            passphrase: PassphraseOptions::default(),
+            breached_passwords_path: None,
+            vault_path: None,
+            default_vault: None,
+            vaults: BTreeMap::new(),
+        }
+    }
+}
+
+/// A named vault of the registry. Unset settings fall back to the global ones.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VaultSettings {
+    pub path: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_retention: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_history_retention: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inactivity_delay: Option<u64>,
+}
+
+fn default_language_code() -> String {
+    "en".to_string()
+}
+
+fn default_backup_retention() -> usize {
+    DEFAULT_BACKUP_RETENTION
+}
+
+fn default_password_history_retention() -> usize {
+    DEFAULT_PASSWORD_HISTORY_RETENTION
+}
+
+fn default_password_rotation_days() -> u64 {
+    DEFAULT_PASSWORD_ROTATION_DAYS
+}
+
+pub fn config_path() -> PathBuf {
+    app_directory(dirs::config_dir()).join(CONFIG_FILE_NAME)
+}
+
+pub fn default_vault_path() -> PathBuf {
+    app_directory(dirs::data_dir()).join(FILE_NAME)
+}
+
+// Falls back to the current directory on platforms without such directories.
+fn app_directory(base: Option<PathBuf>) -> PathBuf {
+    base.map(|base| base.join(APP_DIRECTORY_NAME))
+        .unwrap_or_default()
+}
 
 pub fn read_local_config() -> Result<(), Box<dyn Error>> {
-    if let Ok(mut file) = File::open(LOCAL_CONFIG_PATH) {
+    let path = config_path();
+
+    // The config file used to be created in the directory NeoPass was run from.
+    if !path.exists() && Path::new(LEGACY_CONFIG_PATH).exists() {
+        create_parent_directory(&path)?;
+        fs::copy(LEGACY_CONFIG_PATH, &path)?;
+        fs::remove_file(LEGACY_CONFIG_PATH)?;
+    }
+
+    if let Ok(mut file) = File::open(&path) {
         let mut content = String::new();
         file.read_to_string(&mut content)
             .expect("Failed to read the file");
         let res: Config = serde_json::from_str(&content).expect("Cannot parse local config file");
 
-        *LANGUAGE.lock().unwrap() = res.language_code.to_string();
+        *LANGUAGE.lock().unwrap() = res.language_code.clone();
+        *CONFIG.lock().unwrap() = res;
     } else {
-        let mut file = File::create(LOCAL_CONFIG_PATH)?;
-        let config = Config {
-            language_code: &LANGUAGE.lock().unwrap(),
-        };
-        let contents = serde_json::to_string(&config)?;
+        create_parent_directory(&path)?;
+        let mut file = File::create(&path)?;
+        let contents = serde_json::to_string(&*CONFIG.lock().unwrap())?;
         file.write_all(contents.as_bytes())?;
     }
     Ok(())
 }
 
 pub fn write_local_config() -> Result<(), Box<dyn Error>> {
-    let config = Config {
-        language_code: &LANGUAGE.lock().unwrap(),
+    let mut config = CONFIG.lock().unwrap();
+    config.language_code = LANGUAGE.lock().unwrap().clone();
+
+    let contents = serde_json::to_string(&*config)?;
+    fs::write(config_path(), contents)?;
+
+    Ok(())
+}
+
+/// Picks the vault to open: the `--vault` flag or `NEOPASS_VAULT` first, then the
+/// `default_vault` of the config file, then its `vault_path`, then the data directory.
+/// Names of registered vaults are looked up in the registry, anything else is a path.
+pub fn select_vault(vault: Option<String>) -> Result<(), Box<dyn Error>> {
+    let config = CONFIG.lock().unwrap();
+
+    let (name, path) = match vault.or_else(|| config.default_vault.clone()) {
+        Some(vault) => match config.vaults.get(&vault) {
+            Some(settings) => (Some(vault), settings.path.clone()),
+            None => (None, PathBuf::from(vault)),
+        },
+        None => (
+            None,
+            config.vault_path.clone().unwrap_or_else(default_vault_path),
+        ),
     };
-    let contents = serde_json::to_string(&config)?;
-    fs::write(LOCAL_CONFIG_PATH, contents)?;
+
+    drop(config);
+
+    if path == default_vault_path() {
+        migrate_legacy_vault(&path)?;
+    }
+
+    open_vault(name, path)
+}
+
+pub fn open_vault(name: Option<String>, path: PathBuf) -> Result<(), Box<dyn Error>> {
+    create_parent_directory(&path)?;
+    *VAULT_PATH.lock().unwrap() = path;
+    *CURRENT_VAULT.lock().unwrap() = name;
+
+    lock_vault()
+}
+
+// The vault used to be created in the directory NeoPass was run from, so running
+// it from anywhere else started from an empty vault.
+fn migrate_legacy_vault(path: &Path) -> Result<(), Box<dyn Error>> {
+    let legacy_path = Path::new(FILE_NAME);
+
+    if path.exists() || !legacy_path.exists() {
+        return Ok(());
+    }
+
+    create_parent_directory(path)?;
+    fs::copy(legacy_path, path)?;
+    File::open(path)?.sync_all()?;
+    fs::remove_file(legacy_path)?;
+
+    println!("\n  {} {}", get_translation("vault_moved"), path.display());
 
     Ok(())
 }
 
+pub fn create_parent_directory(path: &Path) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn vault_path() -> PathBuf {
+    VAULT_PATH.lock().unwrap().clone()
+}
+
+/// Settings of the vault currently open, when it comes from the registry.
+pub fn current_vault_settings() -> Option<VaultSettings> {
+    let name = CURRENT_VAULT.lock().unwrap().clone()?;
+
+    CONFIG.lock().unwrap().vaults.get(&name).cloned()
+}
+
+pub fn backup_retention() -> usize {
+    current_vault_settings()
+        .and_then(|settings| settings.backup_retention)
+        .unwrap_or_else(|| CONFIG.lock().unwrap().backup_retention)
+}
+
+/// How many previous passwords every entry keeps.
+pub fn password_history_retention() -> usize {
+    current_vault_settings()
+        .and_then(|settings| settings.password_history_retention)
+        .unwrap_or_else(|| CONFIG.lock().unwrap().password_history_retention)
+}
+
+/// Rules of generated passwords, unless an entry has its own.
+pub fn password_policy() -> PasswordPolicy {
+    CONFIG.lock().unwrap().password_policy.clone()
+}
+
+pub fn inactivity_delay() -> u64 {
+    current_vault_settings()
+        .and_then(|settings| settings.inactivity_delay)
+        .unwrap_or(INACTIVITY_DELAY)
+}
+
+/// Path of a file kept next to the vault, such as `passwords.txt.bak`.
+pub fn vault_sibling_path(suffix: &str) -> PathBuf {
+    let mut path: OsString = vault_path().into_os_string();
+    path.push(suffix);
+
+    PathBuf::from(path)
+}
+
 lazy_static::lazy_static! {
     pub static ref LANGUAGE: Mutex<String> = Mutex::new("en".to_string());
     pub static ref TRANSLATIONS: Mutex<Translations> = Mutex::new(HashMap::new());
+    pub static ref CONFIG: Mutex<Config> = Mutex::new(Config::default());
+    pub static ref VAULT_PATH: Mutex<PathBuf> = Mutex::new(PathBuf::from(FILE_NAME));
+    pub static ref CURRENT_VAULT: Mutex<Option<String>> = Mutex::new(None);
 }
 
 pub const PASSWORD_LENGTH: usize = 20;
-pub const SYMBOLS_TO_USE_IN_PASSWORDS: &str =
-    "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789!*#_-|&@~$";
-pub const FILE_PATH: &str = "passwords.txt";
+pub const DEFAULT_PASSWORD_SYMBOLS: &str = "!*#_-|&@~$";
+pub const AMBIGUOUS_CHARACTERS: &str = "0O1lI|";
+pub const PASSPHRASE_WORDS: usize = 6;
+pub const DERIVATION_MEMORY_KIB: u32 = 64 * 1024;
+pub const DERIVATION_ITERATIONS: u32 = 3;
+pub const APP_DIRECTORY_NAME: &str = "neopass";
+pub const FILE_NAME: &str = "passwords.txt";
+pub const CONFIG_FILE_NAME: &str = "config.json";
+pub const LEGACY_CONFIG_PATH: &str = "local_config.json";
+pub const VAULT_ENV_VARIABLE: &str = "NEOPASS_VAULT";
 pub const INACTIVITY_DELAY: u64 = 5 * 60;
+pub const DEFAULT_BACKUP_RETENTION: usize = 10;
+pub const DEFAULT_PASSWORD_HISTORY_RETENTION: usize = 10;
+pub const DEFAULT_PASSWORD_ROTATION_DAYS: u64 = 365;
+pub const MAX_ATTACHMENT_SIZE: u64 = 1024 * 1024;
 pub const LANGUAGES_AVAILABLE: [Language; 2] = [
     Language {
         label: "English",
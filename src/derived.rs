@@ -0,0 +1,384 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use chrono::Utc;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::error::Error;
+
+use crate::config::{
+    password_history_retention, password_policy, DERIVATION_ITERATIONS, DERIVATION_MEMORY_KIB,
+};
+use crate::entry::Entry;
+use crate::generator::PasswordPolicy;
+use crate::languages::get_translation;
+
+/// Parameters of a password that is derived from the master password, the name of the
+/// entry and its username instead of being stored. The same parameters give the same
+/// password on any machine, as long as the master password is the same.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Derivation {
+    // Name and username of the entry when its password was first derived, so that
+    // renaming the entry does not change its password. Empty in derivations saved
+    // before they were kept, which use those of the entry.
+    #[serde(default)]
+    pub site: String,
+    #[serde(default)]
+    pub login: String,
+    // Increased to get a new password for the same account.
+    pub counter: u32,
+    // Cost of the Argon2id key derivation.
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub policy: PasswordPolicy,
+}
+
+impl Derivation {
+    pub fn new(policy: PasswordPolicy, site: &str, login: &str) -> Self {
+        Derivation {
+            site: site.to_string(),
+            login: login.to_string(),
+            counter: 1,
+            memory_kib: DERIVATION_MEMORY_KIB,
+            iterations: DERIVATION_ITERATIONS,
+            policy,
+        }
+    }
+}
+
+// Endless stream of bytes expanded from a derived key.
+struct KeyStream {
+    key: Vec<u8>,
+    block: u64,
+    bytes: Vec<u8>,
+}
+
+impl KeyStream {
+    fn next_u32(&mut self) -> u32 {
+        while self.bytes.len() < 4 {
+            let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).unwrap();
+            mac.update(&self.block.to_be_bytes());
+            self.bytes.extend(mac.finalize().into_bytes());
+            self.block += 1;
+        }
+
+        let bytes: Vec<u8> = self.bytes.drain(..4).collect();
+        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    // Uniform number below `bound`, rejecting the values that would bias it.
+    fn below(&mut self, bound: usize) -> usize {
+        let bound = bound as u64;
+        let limit = (1u64 << 32) / bound * bound;
+
+        loop {
+            let value = self.next_u32() as u64;
+            if value < limit {
+                return (value % bound) as usize;
+            }
+        }
+    }
+}
+
+/// Derives the password of an account. This algorithm must never change, or derived
+/// passwords would no longer be the ones set on websites.
+pub fn derive_password(
+    master_password: &str,
+    site: &str,
+    login: &str,
+    derivation: &Derivation,
+) -> Result<String, Box<dyn Error>> {
+    let policy = &derivation.policy;
+    policy.validate()?;
+
+    let site = site.trim().to_lowercase();
+    let login = login.trim();
+    // Lengths keep "a:b" + "c" apart from "a" + "b:c".
+    let salt = format!(
+        "neopass-derived-v1|{}|{}|{}|{}|{}",
+        site.len(),
+        site,
+        login.len(),
+        login,
+        derivation.counter
+    );
+
+    let params = match Params::new(derivation.memory_kib, derivation.iterations, 1, Some(32)) {
+        Ok(params) => params,
+        Err(error) => return Err(format!("Invalid derivation parameters: {}", error).into()),
+    };
+    let mut key = vec![0u8; 32];
+    if let Err(error) = Argon2::new(Algorithm::Argon2id, Version::V0x13, params).hash_password_into(
+        master_password.as_bytes(),
+        salt.as_bytes(),
+        &mut key,
+    ) {
+        return Err(format!("Cannot derive the password: {}", error).into());
+    }
+
+    let mut stream = KeyStream {
+        key,
+        block: 0,
+        bytes: Vec::new(),
+    };
+
+    let classes = policy.classes();
+    let all: Vec<char> = classes.concat();
+
+    let mut password: Vec<char> = Vec::with_capacity(policy.length);
+    for class in &classes {
+        for _ in 0..policy.min_per_class {
+            password.push(class[stream.below(class.len())]);
+        }
+    }
+    while password.len() < policy.length {
+        password.push(all[stream.below(all.len())]);
+    }
+
+    // Fisher-Yates, so the characters required by every class are anywhere.
+    for i in (1..password.len()).rev() {
+        let j = stream.below(i + 1);
+        password.swap(i, j);
+    }
+
+    Ok(password.into_iter().collect())
+}
+
+/// Password of an entry, derived when the entry does not store it.
+pub fn entry_password(entry: &Entry, master_password: &str) -> Result<String, Box<dyn Error>> {
+    match &entry.derivation {
+        Some(derivation) if derivation.site.is_empty() => derive_password(
+            master_password,
+            &entry.application,
+            &entry.username,
+            derivation,
+        ),
+        Some(derivation) => derive_password(
+            master_password,
+            &derivation.site,
+            &derivation.login,
+            derivation,
+        ),
+        None => Ok(entry.password.clone()),
+    }
+}
+
+/// Keeps the name and username a derivation uses before they are edited, for
+/// derivations saved before they were kept.
+pub fn keep_derivation_inputs(entry: &mut Entry) {
+    if let Some(derivation) = &mut entry.derivation {
+        if derivation.site.is_empty() {
+            derivation.site = entry.application.clone();
+            derivation.login = entry.username.clone();
+        }
+    }
+}
+
+/// Whether the password of an entry is derived from a name or username it no longer
+/// has.
+pub fn derives_from_former_names(entry: &Entry) -> bool {
+    match &entry.derivation {
+        Some(derivation) if !derivation.site.is_empty() => {
+            derivation.site != entry.application || derivation.login != entry.username
+        }
+        _ => false,
+    }
+}
+
+/// Turns derived passwords into stored ones, before the master password they are
+/// derived from changes.
+pub fn store_derived_passwords(
+    entries: &mut [Entry],
+    master_password: &str,
+) -> Result<(), Box<dyn Error>> {
+    for entry in entries.iter_mut().filter(|e| e.derivation.is_some()) {
+        let password = entry_password(entry, master_password)?;
+        entry.derivation = None;
+        entry.replace_password(password, password_history_retention());
+        entry.modified_at = Some(Utc::now());
+    }
+
+    Ok(())
+}
+
+// Rules offered for derived passwords, as they cannot be tweaked on the fly.
+fn templates() -> Vec<(String, PasswordPolicy)> {
+    vec![
+        (get_translation("template_default"), password_policy()),
+        (
+            get_translation("template_long"),
+            PasswordPolicy {
+                length: 20,
+                symbols: false,
+                ..Default::default()
+            },
+        ),
+        (
+            get_translation("template_short"),
+            PasswordPolicy {
+                length: 12,
+                ..Default::default()
+            },
+        ),
+        (
+            get_translation("template_pin"),
+            PasswordPolicy {
+                length: 4,
+                lowercase: false,
+                uppercase: false,
+                symbols: false,
+                ..Default::default()
+            },
+        ),
+    ]
+}
+
+/// Asks for the parameters of the derived password of an entry. Changing the counter
+/// gives a new password for the same account.
+pub fn edit_derivation(entry: &Entry) -> Result<Derivation, Box<dyn Error>> {
+    let mut derivation = match entry.derivation.clone() {
+        Some(mut derivation) => {
+            // The password stays the same unless the user asks otherwise.
+            if derives_from_former_names(entry) {
+                println!(
+                    "  ⚠ {} {} ({})",
+                    get_translation("derived_from_former_names"),
+                    derivation.site,
+                    derivation.login
+                );
+                if Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!("  {}", get_translation("derive_from_new_names")))
+                    .default(false)
+                    .interact()?
+                {
+                    derivation.site = entry.application.clone();
+                    derivation.login = entry.username.clone();
+                }
+            }
+            derivation
+        }
+        None => {
+            let templates = templates();
+            let selection = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("  {}", get_translation("derivation_template")))
+                .default(0)
+                .items(templates.iter().map(|(name, _)| name).collect::<Vec<_>>())
+                .interact()?;
+            Derivation::new(
+                templates[selection].1.clone(),
+                &entry.application,
+                &entry.username,
+            )
+        }
+    };
+
+    derivation.counter = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("  {}", get_translation("derivation_counter")))
+        .with_initial_text(derivation.counter.to_string())
+        .interact_text()?;
+
+    Ok(derivation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cheap parameters, the real ones take a while in debug builds.
+    fn derivation(counter: u32) -> Derivation {
+        Derivation {
+            site: String::new(),
+            login: String::new(),
+            counter,
+            memory_kib: 64,
+            iterations: 1,
+            policy: PasswordPolicy::default(),
+        }
+    }
+
+    #[test]
+    fn test_derivation_is_deterministic() {
+        let first = derive_password("master", "example.com", "john", &derivation(1)).unwrap();
+        let again = derive_password("master", " Example.com ", "john", &derivation(1)).unwrap();
+
+        assert_eq!(first, again);
+        assert_eq!(first.chars().count(), 20);
+        assert!(PasswordPolicy::default()
+            .classes()
+            .iter()
+            .all(|class| first.chars().any(|c| class.contains(&c))));
+    }
+
+    #[test]
+    fn test_derivation_inputs() {
+        let password = derive_password("master", "example.com", "john", &derivation(1)).unwrap();
+
+        for other in [
+            derive_password("other", "example.com", "john", &derivation(1)),
+            derive_password("master", "example.org", "john", &derivation(1)),
+            derive_password("master", "example.com", "jane", &derivation(1)),
+            derive_password("master", "example.com", "john", &derivation(2)),
+        ] {
+            assert_ne!(other.unwrap(), password);
+        }
+    }
+
+    #[test]
+    fn test_derivation_policy() {
+        let pin = Derivation {
+            policy: PasswordPolicy {
+                length: 6,
+                lowercase: false,
+                uppercase: false,
+                symbols: false,
+                ..Default::default()
+            },
+            ..derivation(1)
+        };
+
+        let password = derive_password("master", "bank", "john", &pin).unwrap();
+
+        assert_eq!(password.len(), 6);
+        assert!(password.chars().all(|c| c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn test_store_derived_passwords() {
+        let mut entries = vec![
+            Entry::new("example.com".to_string(), "john".to_string(), String::new()),
+            Entry::new("Bank".to_string(), "john".to_string(), "stored".to_string()),
+        ];
+        entries[0].derivation = Some(derivation(1));
+        let derived = entry_password(&entries[0], "master").unwrap();
+
+        store_derived_passwords(&mut entries, "master").unwrap();
+
+        assert_eq!(entries[0].derivation, None);
+        assert_eq!(entries[0].password, derived);
+        assert!(entries[0].password_history.is_empty());
+        assert_eq!(entry_password(&entries[1], "other").unwrap(), "stored");
+    }
+
+    #[test]
+    fn test_renaming_keeps_the_password() {
+        let mut entry = Entry::new("example.com".to_string(), "john".to_string(), String::new());
+        entry.derivation = Some(derivation(1));
+        let password = entry_password(&entry, "master").unwrap();
+
+        keep_derivation_inputs(&mut entry);
+        entry.application = "Example".to_string();
+        entry.username = "john@example.com".to_string();
+
+        assert!(derives_from_former_names(&entry));
+        assert_eq!(entry_password(&entry, "master").unwrap(), password);
+    }
+
+    #[test]
+    fn test_known_password() {
+        // Derived passwords must stay the same from one version of NeoPass to the next.
+        assert_eq!(
+            derive_password("master", "example.com", "john", &derivation(1)).unwrap(),
+            "S3N|5j3Xps3y3pUy|SMD"
+        );
+    }
+}
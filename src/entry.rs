@@ -1,114 +1,871 @@
 use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
 
-use rand::{thread_rng, Rng};
-use tabled::Tabled;
+use chrono::{DateTime, Local, Utc};
+use clipboard::{ClipboardContext, ClipboardProvider};
+use console::Term;
+use tabled::settings::object::Rows;
+use tabled::settings::{Disable, Style};
+use tabled::{Table, Tabled};
+use uuid::Uuid;
 
-use dialoguer::{theme::ColorfulTheme, Input, Password};
+use dialoguer::{theme::ColorfulTheme, Confirm, Editor, Input, Password, Select};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    config::{PASSWORD_LENGTH, SYMBOLS_TO_USE_IN_PASSWORDS},
+    attachments::{describe_attachment, Attachment},
+    config::{password_history_retention, password_policy},
+    derived::{edit_derivation, keep_derivation_inputs, Derivation},
+    generator::{generate_for_entry, PasswordPolicy},
+    kinds::{is_valid_expiry_date, EntryKind, KindSchema},
     languages::get_translation,
+    otp::{Otp, OtpKind},
+    strength::ask_new_password,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Entry {
+    pub id: Uuid,
+    #[serde(default, skip_serializing_if = "EntryKind::is_login")]
+    pub kind: EntryKind,
     pub application: String,
     pub username: String,
     pub password: String,
+    // Values of the fields specific to the kind of the entry.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub urls: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub custom_fields: Vec<CustomField>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub otp: Option<Otp>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    // Set when the password is derived from the master password instead of stored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derivation: Option<Derivation>,
+    // Rules of the passwords generated for this entry, when not the default ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_policy: Option<PasswordPolicy>,
+    // Previous passwords, the most recent first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub password_history: Vec<PasswordChange>,
+    // When the current password was set, unknown for entries created before NeoPass kept
+    // track of it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_changed_at: Option<DateTime<Utc>>,
+    // Unknown for entries created before NeoPass kept track of them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<DateTime<Utc>>,
+    // Set while the entry is in the trash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomField {
+    pub name: String,
+    pub value: String,
+    // Concealed values are masked like passwords.
+    #[serde(default)]
+    pub concealed: bool,
+}
+
+/// A password replaced by another one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PasswordChange {
+    pub password: String,
+    pub replaced_at: DateTime<Utc>,
+}
+
+impl CustomField {
+    pub fn displayed_value(&self) -> String {
+        if self.concealed {
+            "********".to_string()
+        } else {
+            self.value.clone()
+        }
+    }
+}
+
+impl Entry {
+    pub fn new(application: String, username: String, password: String) -> Self {
+        let now = Utc::now();
+
+        Entry {
+            id: Uuid::new_v4(),
+            kind: EntryKind::Login,
+            application,
+            username,
+            password,
+            fields: BTreeMap::new(),
+            urls: Vec::new(),
+            notes: String::new(),
+            tags: BTreeSet::new(),
+            custom_fields: Vec::new(),
+            otp: None,
+            attachments: Vec::new(),
+            derivation: None,
+            password_policy: None,
+            password_history: Vec::new(),
+            password_changed_at: Some(now),
+            created_at: Some(now),
+            modified_at: Some(now),
+            last_used_at: None,
+            deleted_at: None,
+        }
+    }
+
+    pub fn is_in_trash(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// Changes the password, keeping the `retention` last ones in the history.
+    pub fn replace_password(&mut self, password: String, retention: usize) {
+        if password == self.password {
+            return;
+        }
+
+        let previous = std::mem::replace(&mut self.password, password);
+        let now = Utc::now();
+
+        if !previous.is_empty() {
+            self.password_history.insert(
+                0,
+                PasswordChange {
+                    password: previous,
+                    replaced_at: now,
+                },
+            );
+        }
+        self.password_changed_at = Some(now);
+        self.password_history.truncate(retention);
+    }
+
+    /// Whether both entries are versions of the same account.
+    pub fn is_same_account(&self, other: &Entry) -> bool {
+        self.id == other.id
+    }
+
+    /// Whether both entries hold the same data, regardless of when they were last used.
+    pub fn has_same_content(&self, other: &Entry) -> bool {
+        Entry {
+            last_used_at: None,
+            ..self.clone()
+        } == Entry {
+            last_used_at: None,
+            ..other.clone()
+        }
+    }
 }
 
 impl Tabled for Entry {
-    const LENGTH: usize = 3;
+    const LENGTH: usize = 7;
 
     fn fields(&self) -> Vec<Cow<'_, str>> {
         vec![
             Cow::Borrowed(&self.application),
+            Cow::Owned(get_translation(self.kind.label())),
             Cow::Borrowed(&self.username),
             Cow::Borrowed(&self.password),
+            Cow::Borrowed(
+                self.fields
+                    .get("expiry_date")
+                    .map(String::as_str)
+                    .unwrap_or_default(),
+            ),
+            Cow::Borrowed(self.urls.first().map(String::as_str).unwrap_or_default()),
+            Cow::Owned(join_list(&self.tags)),
         ]
     }
     fn headers() -> Vec<Cow<'static, str>> {
         vec![
             Cow::Owned(get_translation("application_website")),
+            Cow::Owned(get_translation("kind")),
             Cow::Owned(get_translation("username_email")),
             Cow::Owned(get_translation("password")),
+            Cow::Owned(get_translation("expiry_date")),
+            Cow::Owned(get_translation("url")),
+            Cow::Owned(get_translation("tags")),
         ]
     }
 }
 
-pub fn generate_password(length: usize) -> String {
-    let mut rng = thread_rng();
-    let chars: Vec<char> = (0..length)
-        .map(|_| {
-            SYMBOLS_TO_USE_IN_PASSWORDS
-                .chars()
-                .nth(rng.gen_range(0..SYMBOLS_TO_USE_IN_PASSWORDS.len()))
-                .unwrap()
-        })
-        .collect();
-    chars.into_iter().collect()
+/// Column of the expiry date in the table of entries.
+pub const EXPIRY_COLUMN: usize = 4;
+
+/// Headers of the table of entries showing `kinds`. The name, identifier and secret
+/// columns take the labels of these kinds, joined when they differ.
+pub fn table_headers(kinds: &[EntryKind]) -> Vec<String> {
+    let label = |default: &str, pick: fn(&KindSchema) -> Option<&'static str>| {
+        let mut labels: Vec<&str> = Vec::new();
+        for kind in kinds {
+            if let Some(label) = pick(&kind.schema()) {
+                if !labels.contains(&label) {
+                    labels.push(label);
+                }
+            }
+        }
+
+        if labels.is_empty() {
+            return get_translation(default);
+        }
+        labels
+            .into_iter()
+            .map(get_translation)
+            .collect::<Vec<String>>()
+            .join(" · ")
+    };
+
+    let mut headers: Vec<String> = Entry::headers().into_iter().map(Cow::into_owned).collect();
+    headers[0] = label("application_website", |schema| Some(schema.name));
+    headers[2] = label("username_email", |schema| schema.identifier);
+    headers[3] = label("password", |schema| schema.secret);
+
+    headers
+}
+
+/// Whether some of `kinds` have an expiry date.
+pub fn has_expiry_date(kinds: &[EntryKind]) -> bool {
+    kinds.iter().any(|kind| {
+        kind.schema()
+            .fields
+            .iter()
+            .any(|field| field.key == "expiry_date")
+    })
+}
+
+/// Splits a comma separated list typed by the user.
+pub fn parse_list(input: &str) -> Vec<String> {
+    input
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn join_list<'a>(items: impl IntoIterator<Item = &'a String>) -> String {
+    items
+        .into_iter()
+        .map(String::as_str)
+        .collect::<Vec<&str>>()
+        .join(", ")
 }
 
 pub fn add_a_new_entry(entries: &mut Vec<Entry>) {
     println!("\n  {}\n", get_translation("add_a_new_entry"));
 
-    let application: String = Input::with_theme(&ColorfulTheme::default())
-        .with_prompt("  Application / Website:")
-        .interact_text()
-        .unwrap();
-
-    let username: String = Input::with_theme(&ColorfulTheme::default())
-        .with_prompt("  Username / Email:")
-        .interact_text()
-        .unwrap();
+    let labels: Vec<String> = EntryKind::ALL
+        .iter()
+        .map(|kind| get_translation(kind.label()))
+        .collect();
 
-    let mut password: String = Password::with_theme(&ColorfulTheme::default())
-        .with_prompt("  Password (leave empty for random):")
-        .allow_empty_password(true)
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("  {}", get_translation("kind")))
+        .default(0)
+        .items(&labels)
         .interact()
         .unwrap();
 
-    if password.is_empty() {
-        password = generate_password(PASSWORD_LENGTH);
-    }
+    let mut entry = Entry::new(String::new(), String::new(), String::new());
+    entry.kind = EntryKind::ALL[selection];
+
+    fill_entry(&mut entry, true);
+    edit_details(&mut entry);
 
-    entries.push(Entry {
-        application,
-        username,
-        password,
-    });
+    entries.push(entry);
 }
 
 pub fn modify_entry(entries: &mut [Entry], index: usize) {
     println!("\n  {}\n", get_translation("edit_an_entry"));
 
-    let entry = &entries[index];
+    let entry = &mut entries[index];
+    keep_derivation_inputs(entry);
+    let before = entry.clone();
+
+    fill_entry(entry, false);
+    edit_details(entry);
+
+    if !entry.has_same_content(&before) {
+        entry.modified_at = Some(Utc::now());
+    }
+}
 
-    let application: String = Input::with_theme(&ColorfulTheme::default())
-        .with_prompt("  Application / Website:")
+fn prompt(label: &str) -> String {
+    format!("  {}:", get_translation(label))
+}
+
+// Asks for the values of the fields of the kind of the entry.
+fn fill_entry(entry: &mut Entry, is_new: bool) {
+    let schema = entry.kind.schema();
+    keep_derivation_inputs(entry);
+
+    entry.application = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt(schema.name))
         .with_initial_text(entry.application.clone())
         .interact_text()
         .unwrap();
 
-    let username: String = Input::with_theme(&ColorfulTheme::default())
-        .with_prompt("  Username / Email:")
-        .with_initial_text(entry.username.clone())
-        .interact_text()
-        .unwrap();
+    if let Some(identifier) = schema.identifier {
+        entry.username = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt(identifier))
+            .with_initial_text(entry.username.clone())
+            .allow_empty(!entry.kind.is_login())
+            .interact_text()
+            .unwrap();
+    }
+
+    if let Some(secret) = schema.secret {
+        if entry.kind == EntryKind::SshKey {
+            let private_key = edit_multiline(secret, &entry.password);
+            entry.replace_password(private_key, password_history_retention());
+        } else if entry.kind.is_login() {
+            // Derived passwords are not stored, only what is needed to derive them again.
+            let modes = [
+                get_translation("store_password"),
+                get_translation("derive_password"),
+            ];
+            let derive = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt(prompt("password"))
+                .default(entry.derivation.is_some() as usize)
+                .items(&modes)
+                .interact()
+                .unwrap()
+                == 1;
+
+            if derive {
+                // When the prompts are interrupted, the password is left as it was.
+                if let Ok(derivation) = edit_derivation(entry) {
+                    entry.derivation = Some(derivation);
+                    entry.replace_password(String::new(), password_history_retention());
+                }
+            } else {
+                // An entry whose password was derived has none to keep.
+                let needs_password = is_new || entry.derivation.take().is_some();
+
+                let password = ask_new_password(
+                    "  Password (leave empty to generate one):",
+                    &[&entry.application, &entry.username],
+                    true,
+                )
+                .unwrap();
+
+                let password = if !password.is_empty() {
+                    Some(password)
+                } else {
+                    match generate_for_entry(&mut entry.password_policy).unwrap() {
+                        Some(password) => Some(password),
+                        // A new entry still needs a password.
+                        None if needs_password => Some(
+                            password_policy()
+                                .generate()
+                                .or_else(|_| PasswordPolicy::default().generate())
+                                .unwrap(),
+                        ),
+                        None => None,
+                    }
+                };
+
+                if let Some(password) = password {
+                    entry.replace_password(password, password_history_retention());
+                }
+            }
+        } else {
+            let value = ask_concealed(secret, is_new);
+            if is_new || !value.is_empty() {
+                entry.replace_password(value, password_history_retention());
+            }
+        }
+    }
+
+    for field in schema.fields {
+        let current = entry.fields.get(field.key).cloned().unwrap_or_default();
+
+        let value = if field.concealed {
+            match ask_concealed(field.key, is_new) {
+                value if value.is_empty() && !is_new => current,
+                value => value,
+            }
+        } else if field.multiline {
+            edit_multiline(field.key, &current)
+        } else {
+            Input::with_theme(&ColorfulTheme::default())
+                .with_prompt(prompt(field.key))
+                .with_initial_text(current)
+                .allow_empty(true)
+                .validate_with(|input: &String| -> Result<(), String> {
+                    if field.key == "expiry_date"
+                        && !input.is_empty()
+                        && !is_valid_expiry_date(input)
+                    {
+                        Err(get_translation("invalid_expiry_date"))
+                    } else {
+                        Ok(())
+                    }
+                })
+                .interact_text()
+                .unwrap()
+        };
 
-    let mut password: String = Password::with_theme(&ColorfulTheme::default())
-        .with_prompt("  Password (leave empty for random):")
+        if value.is_empty() {
+            entry.fields.remove(field.key);
+        } else {
+            entry.fields.insert(field.key.to_string(), value);
+        }
+    }
+
+    if entry.kind == EntryKind::SecureNote {
+        entry.notes = edit_multiline("notes", &entry.notes);
+    }
+}
+
+fn ask_concealed(label: &str, is_new: bool) -> String {
+    let prompt = if is_new {
+        prompt(label)
+    } else {
+        format!(
+            "  {} ({}):",
+            get_translation(label),
+            get_translation("leave_empty_to_keep")
+        )
+    };
+
+    Password::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
         .allow_empty_password(true)
         .interact()
+        .unwrap()
+}
+
+// Values spanning several lines are written in the user's editor.
+fn edit_multiline(label: &str, current: &str) -> String {
+    println!("{}", prompt(label));
+
+    match Editor::new().edit(current).unwrap() {
+        Some(value) => value.trim_end().to_string(),
+        None => current.to_string(),
+    }
+}
+
+// URLs, notes, tags and custom fields are only asked for when the user wants to.
+fn edit_details(entry: &mut Entry) {
+    let edit = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("  {}", get_translation("edit_details")))
+        .default(false)
+        .interact()
         .unwrap();
 
-    if password.is_empty() {
-        password = generate_password(PASSWORD_LENGTH);
+    if !edit {
+        return;
     }
 
-    entries[index] = Entry {
-        application,
-        username,
-        password,
-    };
+    let urls: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("  URLs (comma separated):")
+        .with_initial_text(join_list(&entry.urls))
+        .allow_empty(true)
+        .interact_text()
+        .unwrap();
+    entry.urls = parse_list(&urls);
+
+    let tags: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("  Tags (comma separated):")
+        .with_initial_text(join_list(&entry.tags))
+        .allow_empty(true)
+        .interact_text()
+        .unwrap();
+    entry.tags = parse_list(&tags).into_iter().collect();
+
+    loop {
+        let otp: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("  One-time password (otpauth:// URI or secret):")
+            .with_initial_text(entry.otp.as_ref().map(Otp::to_uri).unwrap_or_default())
+            .allow_empty(true)
+            .interact_text()
+            .unwrap();
+
+        if otp.trim().is_empty() {
+            entry.otp = None;
+            break;
+        }
+
+        match Otp::parse(&otp) {
+            Ok(otp) => {
+                entry.otp = Some(otp);
+                break;
+            }
+            Err(error) => println!("  {}", error),
+        }
+    }
+
+    // Secure notes already asked for them.
+    let edit_notes = entry.kind != EntryKind::SecureNote
+        && Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("  {}", get_translation("edit_notes")))
+            .default(false)
+            .interact()
+            .unwrap();
+
+    if edit_notes {
+        entry.notes = edit_multiline("notes", &entry.notes);
+    }
+
+    edit_custom_fields(&mut entry.custom_fields);
+}
+
+fn edit_custom_fields(fields: &mut Vec<CustomField>) {
+    loop {
+        let mut items: Vec<String> = fields
+            .iter()
+            .map(|field| format!("{}: {}", field.name, field.displayed_value()))
+            .collect();
+        items.push(get_translation("add_custom_field"));
+        items.push(get_translation("done"));
+
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("  {}", get_translation("custom_fields")))
+            .default(items.len() - 1)
+            .items(&items)
+            .interact()
+            .unwrap();
+
+        if selection > fields.len() {
+            return;
+        }
+
+        let field = fields.get(selection);
+
+        let name: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("  Name (leave empty to remove the field):")
+            .with_initial_text(field.map(|f| f.name.clone()).unwrap_or_default())
+            .allow_empty(true)
+            .interact_text()
+            .unwrap();
+
+        if name.trim().is_empty() {
+            if selection < fields.len() {
+                fields.remove(selection);
+            }
+            continue;
+        }
+
+        let concealed = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("  {}", get_translation("conceal_field")))
+            .default(field.map(|f| f.concealed).unwrap_or_default())
+            .interact()
+            .unwrap();
+
+        let value: String = if concealed {
+            // Concealed values are not shown, so an empty one keeps the current value.
+            let value = Password::with_theme(&ColorfulTheme::default())
+                .with_prompt(match field {
+                    Some(_) => "  Value (leave empty to keep it):",
+                    None => "  Value:",
+                })
+                .allow_empty_password(true)
+                .interact()
+                .unwrap();
+
+            match field {
+                Some(field) if value.is_empty() => field.value.clone(),
+                _ => value,
+            }
+        } else {
+            Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("  Value:")
+                .with_initial_text(field.map(|f| f.value.clone()).unwrap_or_default())
+                .allow_empty(true)
+                .interact_text()
+                .unwrap()
+        };
+
+        let field = CustomField {
+            name: name.trim().to_string(),
+            value,
+            concealed,
+        };
+
+        if selection < fields.len() {
+            fields[selection] = field;
+        } else {
+            fields.push(field);
+        }
+    }
+}
+
+fn format_date(date: &Option<DateTime<Utc>>) -> String {
+    match date {
+        Some(date) => date
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string(),
+        None => "—".to_string(),
+    }
+}
+
+fn describe_otp(otp: &Otp) -> String {
+    match (&otp.kind, otp.current_code()) {
+        (_, Some((code, remaining))) => format!("{}  ({} s)", code, remaining),
+        (OtpKind::Hotp { counter }, _) => format!("HOTP #{}", counter),
+        (OtpKind::Totp { .. }, None) => "—".to_string(),
+    }
+}
+
+/// Table of the details of an entry, with its secrets masked.
+pub fn details_table(entry: &Entry) -> String {
+    let schema = entry.kind.schema();
+
+    let mut rows = vec![
+        [get_translation(schema.name), entry.application.clone()],
+        [get_translation("kind"), get_translation(entry.kind.label())],
+    ];
+
+    if let Some(identifier) = schema.identifier {
+        rows.push([get_translation(identifier), entry.username.clone()]);
+    }
+    if let Some(secret) = schema.secret {
+        rows.push([get_translation(secret), "********".to_string()]);
+    }
+    for field in schema.fields {
+        if let Some(value) = entry.fields.get(field.key) {
+            let value = if field.concealed {
+                "********".to_string()
+            } else {
+                value.clone()
+            };
+            rows.push([get_translation(field.key), value]);
+        }
+    }
+
+    if let Some(otp) = &entry.otp {
+        rows.push([get_translation("one_time_code"), describe_otp(otp)]);
+    }
+    for url in &entry.urls {
+        rows.push([get_translation("url"), url.clone()]);
+    }
+    if !entry.tags.is_empty() {
+        rows.push([get_translation("tags"), join_list(&entry.tags)]);
+    }
+    for field in &entry.custom_fields {
+        rows.push([field.name.clone(), field.displayed_value()]);
+    }
+    if !entry.notes.is_empty() {
+        rows.push([get_translation("notes"), entry.notes.clone()]);
+    }
+    for attachment in &entry.attachments {
+        rows.push([
+            get_translation("attachment"),
+            describe_attachment(attachment),
+        ]);
+    }
+
+    rows.extend([
+        [
+            get_translation("created_at"),
+            format_date(&entry.created_at),
+        ],
+        [
+            get_translation("modified_at"),
+            format_date(&entry.modified_at),
+        ],
+        [
+            get_translation("last_used_at"),
+            format_date(&entry.last_used_at),
+        ],
+        ["ID".to_string(), entry.id.to_string()],
+    ]);
+
+    let mut table = Table::new(rows);
+    table
+        .with(Style::rounded())
+        .with(Disable::row(Rows::first()));
+
+    table.to_string()
+}
+
+fn details_lines(entry: &Entry) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    lines.extend(
+        details_table(entry)
+            .lines()
+            .map(|line| format!("  {}", line)),
+    );
+    lines.push(String::new());
+    lines.push(format!("  {}", get_translation("press_any_key")));
+
+    lines
+}
+
+pub fn display_entry_details(entry: &Entry) -> Result<(), Box<dyn Error>> {
+    let term = Term::stderr();
+    let is_totp = matches!(
+        entry.otp,
+        Some(Otp {
+            kind: OtpKind::Totp { .. },
+            ..
+        })
+    );
+
+    // Keys are read on another thread, so the one-time code can count down meanwhile.
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || sender.send(Term::stderr().read_key()));
+
+    loop {
+        let lines = details_lines(entry);
+        for line in &lines {
+            term.write_line(line)?;
+        }
+
+        let key = if is_totp {
+            match receiver.recv_timeout(Duration::from_secs(1)) {
+                Err(RecvTimeoutError::Timeout) => {
+                    term.clear_last_lines(lines.len())?;
+                    continue;
+                }
+                key => key.ok(),
+            }
+        } else {
+            receiver.recv().ok()
+        };
+
+        if let Some(key) = key {
+            key?;
+        }
+
+        return Ok(());
+    }
+}
+
+/// Lists the previous passwords of an entry. Selecting one copies it to the clipboard.
+pub fn display_password_history(entry: &Entry) -> Result<(), Box<dyn Error>> {
+    println!(
+        "\n  {} {}\n",
+        get_translation("password_history_of"),
+        entry.application
+    );
+
+    if entry.password_history.is_empty() {
+        println!("  {}", get_translation("no_password_history"));
+        Term::stderr().read_key()?;
+        return Ok(());
+    }
+
+    let items: Vec<String> = entry
+        .password_history
+        .iter()
+        .map(|change| {
+            format!(
+                "{}  {}  ********",
+                get_translation("replaced_at"),
+                format_date(&Some(change.replaced_at))
+            )
+        })
+        .collect();
+
+    let mut selection = 0;
+
+    loop {
+        selection = match Select::with_theme(&ColorfulTheme::default())
+            .default(selection)
+            .items(&items)
+            .interact_opt()?
+        {
+            Some(selection) => selection,
+            None => return Ok(()),
+        };
+
+        let mut cp: ClipboardContext = ClipboardProvider::new()?;
+        cp.set_contents(entry.password_history[selection].password.clone())?;
+
+        println!("  {}", get_translation("password_copied"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::languages::read_locales;
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(
+            parse_list(" work, , personal ,mail"),
+            vec!["work", "personal", "mail"]
+        );
+        assert!(parse_list("  ").is_empty());
+    }
+
+    #[test]
+    fn test_table_headers() {
+        read_locales();
+
+        let logins = table_headers(&[EntryKind::Login, EntryKind::Login]);
+        let cards = table_headers(&[EntryKind::Card]);
+        let mixed = table_headers(&[EntryKind::Login, EntryKind::Card, EntryKind::SecureNote]);
+        let notes = table_headers(&[EntryKind::SecureNote]);
+
+        assert_eq!(logins.len(), Entry::LENGTH);
+        assert_eq!(
+            logins[..4],
+            [
+                "Application / Website",
+                "Kind",
+                "Username / Email",
+                "Password"
+            ]
+        );
+        assert_eq!(cards[0], "Title");
+        assert_eq!(cards[2], "Cardholder");
+        assert_eq!(cards[3], "Card number");
+        assert_eq!(mixed[0], "Application / Website · Title");
+        assert_eq!(mixed[2], "Username / Email · Cardholder");
+        assert_eq!(mixed[3], "Password · Card number");
+        // Notes have neither identifier nor secret, the columns keep their usual names.
+        assert_eq!(notes[2], "Username / Email");
+        assert_eq!(notes[3], "Password");
+
+        assert!(!has_expiry_date(&[EntryKind::Login, EntryKind::SecureNote]));
+        assert!(has_expiry_date(&[EntryKind::Login, EntryKind::Card]));
+    }
+
+    #[test]
+    fn test_replace_password() {
+        let mut entry = Entry::new("Mail".to_string(), "john".to_string(), "1".to_string());
+
+        entry.replace_password("1".to_string(), 2);
+        assert!(entry.password_history.is_empty());
+
+        entry.replace_password("2".to_string(), 2);
+        entry.replace_password("3".to_string(), 2);
+        entry.replace_password("4".to_string(), 2);
+
+        assert_eq!(entry.password, "4");
+        let history: Vec<&str> = entry
+            .password_history
+            .iter()
+            .map(|change| change.password.as_str())
+            .collect();
+        assert_eq!(history, vec!["3", "2"]);
+    }
+
+    #[test]
+    fn test_no_password_history() {
+        let mut entry = Entry::new("Mail".to_string(), "john".to_string(), "1".to_string());
+
+        entry.replace_password("2".to_string(), 0);
+
+        assert!(entry.password_history.is_empty());
+    }
 }
@@ -0,0 +1,354 @@
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Input, Select};
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+use crate::config::{
+    password_policy, write_local_config, AMBIGUOUS_CHARACTERS, CONFIG, DEFAULT_PASSWORD_SYMBOLS,
+    PASSWORD_LENGTH,
+};
+use crate::languages::get_translation;
+use crate::passphrase::generate_passphrase_interactively;
+use crate::strength::{describe_strength, estimate};
+use crate::utils::clear_screen;
+
+/// Rules followed by generated passwords, for websites that do not accept any of them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PasswordPolicy {
+    pub length: usize,
+    pub lowercase: bool,
+    pub uppercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+    // Symbols to pick from when `symbols` is set.
+    pub allowed_symbols: String,
+    // Characters never used, whatever their class.
+    pub excluded: String,
+    // Leaves out characters that are easily mistaken for one another, such as 0 and O.
+    pub exclude_ambiguous: bool,
+    // Minimum number of characters of every enabled class.
+    pub min_per_class: usize,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            length: PASSWORD_LENGTH,
+            lowercase: true,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+            allowed_symbols: DEFAULT_PASSWORD_SYMBOLS.to_string(),
+            excluded: String::new(),
+            exclude_ambiguous: false,
+            min_per_class: 1,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    // Characters of every enabled class, once exclusions are applied.
+    pub(crate) fn classes(&self) -> Vec<Vec<char>> {
+        let classes = [
+            (self.lowercase, "abcdefghijklmnopqrstuvwxyz"),
+            (self.uppercase, "ABCDEFGHIJKLMNOPQRSTUVWXYZ"),
+            (self.digits, "0123456789"),
+            (self.symbols, self.allowed_symbols.as_str()),
+        ];
+
+        classes
+            .iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, characters)| {
+                let mut characters: Vec<char> = characters
+                    .chars()
+                    .filter(|c| !self.excluded.contains(*c))
+                    .filter(|c| !(self.exclude_ambiguous && AMBIGUOUS_CHARACTERS.contains(*c)))
+                    .collect();
+                characters.sort_unstable();
+                characters.dedup();
+                characters
+            })
+            .collect()
+    }
+
+    /// Checks that passwords can be generated with these rules.
+    pub fn validate(&self) -> Result<(), String> {
+        let classes = self.classes();
+
+        if classes.is_empty() {
+            return Err(get_translation("policy_no_class"));
+        }
+        if classes.iter().any(|class| class.is_empty()) {
+            return Err(get_translation("policy_empty_class"));
+        }
+        if self.length == 0 || self.length < self.min_per_class * classes.len() {
+            return Err(get_translation("policy_too_short"));
+        }
+
+        Ok(())
+    }
+
+    pub fn generate(&self) -> Result<String, Box<dyn Error>> {
+        self.generate_with(&mut thread_rng())
+    }
+
+    pub fn generate_with(&self, rng: &mut impl Rng) -> Result<String, Box<dyn Error>> {
+        self.validate()?;
+
+        let classes = self.classes();
+        let all: Vec<char> = classes.concat();
+
+        let mut password: Vec<char> = Vec::with_capacity(self.length);
+        for class in &classes {
+            for _ in 0..self.min_per_class {
+                password.push(*class.choose(rng).unwrap());
+            }
+        }
+        while password.len() < self.length {
+            password.push(*all.choose(rng).unwrap());
+        }
+
+        // The characters required by every class must not always come first.
+        password.shuffle(rng);
+
+        Ok(password.into_iter().collect())
+    }
+}
+
+pub fn on_off(value: bool) -> String {
+    if value {
+        get_translation("on")
+    } else {
+        get_translation("off")
+    }
+}
+
+fn ask_number(prompt: &str, current: usize) -> Result<usize, Box<dyn Error>> {
+    Ok(Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("  {}", get_translation(prompt)))
+        .with_initial_text(current.to_string())
+        .interact_text()?)
+}
+
+fn ask_characters(prompt: &str, current: &str) -> Result<String, Box<dyn Error>> {
+    Ok(Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("  {}", get_translation(prompt)))
+        .with_initial_text(current)
+        .allow_empty(true)
+        .interact_text()?)
+}
+
+/// Generator screen: previews a password following `policy`, whose rules can be
+/// changed until the user picks a password. `None` when the user leaves.
+pub fn generate_interactively(
+    policy: &mut PasswordPolicy,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let mut candidate = policy.generate().ok();
+    let mut selection = 0;
+
+    loop {
+        clear_screen()?;
+        println!("\n  {}\n", get_translation("password_generator"));
+
+        match (&candidate, policy.validate()) {
+            (Some(candidate), Ok(())) => println!(
+                "  {}\n  {}\n",
+                style(candidate).bold(),
+                describe_strength(&estimate(candidate, &[]))
+            ),
+            (_, Err(error)) => println!("  ❌ {}\n", error),
+            (None, Ok(())) => println!(),
+        }
+
+        let items = [
+            get_translation("use_password"),
+            get_translation("regenerate"),
+            format!("{}: {}", get_translation("policy_length"), policy.length),
+            format!(
+                "{}: {}",
+                get_translation("policy_lowercase"),
+                on_off(policy.lowercase)
+            ),
+            format!(
+                "{}: {}",
+                get_translation("policy_uppercase"),
+                on_off(policy.uppercase)
+            ),
+            format!(
+                "{}: {}",
+                get_translation("policy_digits"),
+                on_off(policy.digits)
+            ),
+            format!(
+                "{}: {}",
+                get_translation("policy_symbols"),
+                on_off(policy.symbols)
+            ),
+            format!(
+                "{}: {}",
+                get_translation("policy_allowed_symbols"),
+                policy.allowed_symbols
+            ),
+            format!(
+                "{}: {}",
+                get_translation("policy_excluded"),
+                policy.excluded
+            ),
+            format!(
+                "{}: {}",
+                get_translation("policy_exclude_ambiguous"),
+                on_off(policy.exclude_ambiguous)
+            ),
+            format!(
+                "{}: {}",
+                get_translation("policy_min_per_class"),
+                policy.min_per_class
+            ),
+            get_translation("policy_save_as_default"),
+            get_translation("generate_passphrase"),
+        ];
+
+        selection = match Select::with_theme(&ColorfulTheme::default())
+            .default(selection)
+            .items(&items)
+            .interact_opt()?
+        {
+            Some(selection) => selection,
+            None => return Ok(None),
+        };
+
+        match selection {
+            0 => {
+                if let (Some(candidate), Ok(())) = (&candidate, policy.validate()) {
+                    return Ok(Some(candidate.clone()));
+                }
+            }
+            1 => {}
+            2 => policy.length = ask_number("policy_length", policy.length)?,
+            3 => policy.lowercase = !policy.lowercase,
+            4 => policy.uppercase = !policy.uppercase,
+            5 => policy.digits = !policy.digits,
+            6 => policy.symbols = !policy.symbols,
+            7 => {
+                policy.allowed_symbols =
+                    ask_characters("policy_allowed_symbols", &policy.allowed_symbols)?
+            }
+            8 => policy.excluded = ask_characters("policy_excluded", &policy.excluded)?,
+            9 => policy.exclude_ambiguous = !policy.exclude_ambiguous,
+            10 => policy.min_per_class = ask_number("policy_min_per_class", policy.min_per_class)?,
+            11 => {
+                CONFIG.lock().unwrap().password_policy = policy.clone();
+                write_local_config()?;
+            }
+            _ => {
+                if let Some(passphrase) = generate_passphrase_interactively()? {
+                    return Ok(Some(passphrase));
+                }
+            }
+        }
+
+        candidate = policy.generate().ok();
+    }
+}
+
+/// Password for an entry, from the generator screen. The rules used are remembered
+/// by the entry when they differ from the default ones.
+pub fn generate_for_entry(
+    entry_policy: &mut Option<PasswordPolicy>,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let default_policy = password_policy();
+    let mut policy = entry_policy
+        .clone()
+        .unwrap_or_else(|| default_policy.clone());
+
+    let password = generate_interactively(&mut policy)?;
+
+    if password.is_some() {
+        *entry_policy = (policy != default_policy).then_some(policy);
+    }
+
+    Ok(password)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(password: &str, class: fn(&char) -> bool) -> usize {
+        password.chars().filter(class).count()
+    }
+
+    #[test]
+    fn test_default_policy() {
+        let password = PasswordPolicy::default().generate().unwrap();
+
+        assert_eq!(password.chars().count(), PASSWORD_LENGTH);
+        assert!(count(&password, char::is_ascii_lowercase) >= 1);
+        assert!(count(&password, char::is_ascii_uppercase) >= 1);
+        assert!(count(&password, char::is_ascii_digit) >= 1);
+        assert!(password
+            .chars()
+            .any(|c| DEFAULT_PASSWORD_SYMBOLS.contains(c)));
+    }
+
+    #[test]
+    fn test_minimum_per_class() {
+        let policy = PasswordPolicy {
+            length: 12,
+            symbols: false,
+            min_per_class: 4,
+            ..Default::default()
+        };
+
+        for _ in 0..20 {
+            let password = policy.generate().unwrap();
+
+            assert_eq!(count(&password, char::is_ascii_lowercase), 4);
+            assert_eq!(count(&password, char::is_ascii_uppercase), 4);
+            assert_eq!(count(&password, char::is_ascii_digit), 4);
+        }
+    }
+
+    #[test]
+    fn test_exclusions() {
+        let policy = PasswordPolicy {
+            length: 200,
+            excluded: "|~".to_string(),
+            exclude_ambiguous: true,
+            ..Default::default()
+        };
+
+        let password = policy.generate().unwrap();
+
+        assert!(!password
+            .chars()
+            .any(|c| "|~".contains(c) || AMBIGUOUS_CHARACTERS.contains(c)));
+    }
+
+    #[test]
+    fn test_invalid_policies() {
+        let no_class = PasswordPolicy {
+            lowercase: false,
+            uppercase: false,
+            digits: false,
+            symbols: false,
+            ..Default::default()
+        };
+        let empty_class = PasswordPolicy {
+            excluded: "0123456789".to_string(),
+            ..Default::default()
+        };
+        let too_short = PasswordPolicy {
+            length: 3,
+            ..Default::default()
+        };
+
+        assert!(no_class.generate().is_err());
+        assert!(empty_class.generate().is_err());
+        assert!(too_short.generate().is_err());
+    }
+}
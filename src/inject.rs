@@ -0,0 +1,237 @@
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::ops::Range;
+use std::path::Path;
+
+const SCHEME: &str = "neopass://";
+
+/// A secret referenced in a template, as `neopass://<vault>/<entry>/<field>`. An empty
+/// vault is the one NeoPass opens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub vault: String,
+    pub entry: String,
+    pub field: String,
+}
+
+/// A `{{ neopass://… }}` placeholder of a template.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placeholder {
+    // Where it is in the template, braces included.
+    pub range: Range<usize>,
+    pub line: usize,
+    // What is between the braces.
+    pub text: String,
+    // `None` when the reference is malformed.
+    pub reference: Option<Reference>,
+}
+
+// Names containing slashes are written with `%2F`, like in URLs.
+fn percent_decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = segment.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+pub fn parse_reference(text: &str) -> Option<Reference> {
+    let segments: Vec<&str> = text.strip_prefix(SCHEME)?.split('/').collect();
+
+    match segments[..] {
+        [vault, entry, field] if !entry.is_empty() && !field.is_empty() => Some(Reference {
+            vault: percent_decode(vault)?,
+            entry: percent_decode(entry)?,
+            field: percent_decode(field)?,
+        }),
+        _ => None,
+    }
+}
+
+/// Placeholders of a template. Other `{{ … }}` are left alone, so templates can be
+/// used by other tools too.
+pub fn placeholders(template: &str) -> Vec<Placeholder> {
+    let mut placeholders = Vec::new();
+    let mut position = 0;
+
+    while let Some(start) = template[position..].find("{{") {
+        let start = position + start;
+        let end = match template[start + 2..].find("}}") {
+            Some(end) => start + 2 + end + 2,
+            None => break,
+        };
+
+        let text = template[start + 2..end - 2].trim();
+        if text.starts_with(SCHEME) {
+            placeholders.push(Placeholder {
+                range: start..end,
+                line: template[..start].matches('\n').count() + 1,
+                text: text.to_string(),
+                reference: parse_reference(text),
+            });
+        }
+
+        position = end;
+    }
+
+    placeholders
+}
+
+/// Replaces every placeholder with its value, given in the same order.
+pub fn render(template: &str, placeholders: &[Placeholder], values: &[String]) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut position = 0;
+
+    for (placeholder, value) in placeholders.iter().zip(values) {
+        output.push_str(&template[position..placeholder.range.start]);
+        output.push_str(value);
+        position = placeholder.range.end;
+    }
+    output.push_str(&template[position..]);
+
+    output
+}
+
+/// Refuses to write secrets over a file every user of the machine can read, or over a
+/// symbolic link. `write_private_file` checks it again on the file it opens.
+pub fn check_output_permissions(path: &Path) -> Result<(), Box<dyn Error>> {
+    #[cfg(unix)]
+    {
+        if let Ok(metadata) = std::fs::symlink_metadata(path) {
+            check_private_metadata(path, &metadata)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn check_private_metadata(path: &Path, metadata: &std::fs::Metadata) -> Result<(), Box<dyn Error>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if metadata.file_type().is_symlink() {
+        return Err(format!(
+            "Refusing to write secrets to {}, which is a symbolic link.",
+            path.display()
+        )
+        .into());
+    }
+    if metadata.permissions().mode() & 0o004 != 0 {
+        return Err(format!(
+            "Refusing to write secrets to {}, which every user can read. Remove its permissions for others first (chmod o-r).",
+            path.display()
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Writes a file only its owner can read, unless it already exists. Symbolic links are
+/// not followed, and the permissions are checked on the opened file so that it cannot be
+/// swapped in between.
+pub fn write_private_file(path: &Path, content: &str) -> Result<(), Box<dyn Error>> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600).custom_flags(libc::O_NOFOLLOW);
+    }
+
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    check_private_metadata(path, &file.metadata()?)?;
+
+    file.set_len(0)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const TEMPLATE: &str = "\
+        user = {{ neopass://work/postgres%2Fprod/username }}\n\
+        password = {{neopass:///postgres%2Fprod/password}}\n\
+        name = {{ app_name }}\n\
+        broken = {{ neopass://work/postgres }}\n";
+
+    #[test]
+    fn test_placeholders() {
+        let placeholders = placeholders(TEMPLATE);
+
+        assert_eq!(placeholders.len(), 3);
+        assert_eq!(
+            placeholders[0].reference,
+            Some(Reference {
+                vault: "work".to_string(),
+                entry: "postgres/prod".to_string(),
+                field: "username".to_string(),
+            })
+        );
+        assert_eq!(placeholders[1].line, 2);
+        assert_eq!(placeholders[1].reference.as_ref().unwrap().vault, "");
+        assert_eq!(placeholders[2].text, "neopass://work/postgres");
+        assert_eq!(placeholders[2].reference, None);
+    }
+
+    #[test]
+    fn test_render() {
+        let placeholders = placeholders(TEMPLATE);
+        let values = ["john", "hunter2", "?"].map(|value| value.to_string());
+
+        assert_eq!(
+            render(TEMPLATE, &placeholders, &values),
+            "user = john\npassword = hunter2\nname = {{ app_name }}\nbroken = ?\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("neopass-inject-{}", uuid::Uuid::new_v4()));
+
+        check_output_permissions(&path).unwrap();
+        write_private_file(&path, "secret").unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        check_output_permissions(&path).unwrap();
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(check_output_permissions(&path).is_err());
+        assert!(write_private_file(&path, "other secret").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "secret");
+
+        let link = path.with_extension("link");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        assert!(check_output_permissions(&link).is_err());
+        assert!(write_private_file(&link, "other secret").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "secret");
+
+        fs::remove_file(link).unwrap();
+        fs::remove_file(path).unwrap();
+    }
+}
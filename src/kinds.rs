@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+
+/// What an entry holds. Every kind gives its own meaning to the `username` and
+/// `password` of an entry, and has a few fields of its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    #[default]
+    Login,
+    SecureNote,
+    Card,
+    Identity,
+    ApiToken,
+    SshKey,
+    WifiNetwork,
+}
+
+/// A field specific to a kind of entry. Its key is also the key of its translation.
+pub struct FieldSpec {
+    pub key: &'static str,
+    // Masked like passwords.
+    pub concealed: bool,
+    // Written in the user's editor.
+    pub multiline: bool,
+}
+
+pub struct KindSchema {
+    // Label of the name of the entry, stored in `application`.
+    pub name: &'static str,
+    // Label of `username`, when this kind uses it.
+    pub identifier: Option<&'static str>,
+    // Label of `password`, when this kind uses it. It is the value copied with Space.
+    pub secret: Option<&'static str>,
+    pub fields: &'static [FieldSpec],
+}
+
+const fn field(key: &'static str) -> FieldSpec {
+    FieldSpec {
+        key,
+        concealed: false,
+        multiline: false,
+    }
+}
+
+const fn concealed(key: &'static str) -> FieldSpec {
+    FieldSpec {
+        key,
+        concealed: true,
+        multiline: false,
+    }
+}
+
+const fn multiline(key: &'static str) -> FieldSpec {
+    FieldSpec {
+        key,
+        concealed: false,
+        multiline: true,
+    }
+}
+
+const CARD_FIELDS: &[FieldSpec] = &[field("expiry_date"), concealed("cvv"), concealed("pin")];
+const IDENTITY_FIELDS: &[FieldSpec] = &[
+    field("email"),
+    field("phone"),
+    multiline("address"),
+    field("birth_date"),
+];
+const API_TOKEN_FIELDS: &[FieldSpec] = &[field("expiry_date")];
+const SSH_KEY_FIELDS: &[FieldSpec] = &[concealed("passphrase")];
+const WIFI_NETWORK_FIELDS: &[FieldSpec] = &[field("security")];
+
+impl EntryKind {
+    pub const ALL: [EntryKind; 7] = [
+        EntryKind::Login,
+        EntryKind::SecureNote,
+        EntryKind::Card,
+        EntryKind::Identity,
+        EntryKind::ApiToken,
+        EntryKind::SshKey,
+        EntryKind::WifiNetwork,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            EntryKind::Login => "kind_login",
+            EntryKind::SecureNote => "kind_secure_note",
+            EntryKind::Card => "kind_card",
+            EntryKind::Identity => "kind_identity",
+            EntryKind::ApiToken => "kind_api_token",
+            EntryKind::SshKey => "kind_ssh_key",
+            EntryKind::WifiNetwork => "kind_wifi_network",
+        }
+    }
+
+    pub fn is_login(&self) -> bool {
+        *self == EntryKind::Login
+    }
+
+    pub fn schema(&self) -> KindSchema {
+        match self {
+            EntryKind::Login => KindSchema {
+                name: "application_website",
+                identifier: Some("username_email"),
+                secret: Some("password"),
+                fields: &[],
+            },
+            // The content of a secure note is the notes of the entry.
+            EntryKind::SecureNote => KindSchema {
+                name: "title",
+                identifier: None,
+                secret: None,
+                fields: &[],
+            },
+            EntryKind::Card => KindSchema {
+                name: "title",
+                identifier: Some("cardholder"),
+                secret: Some("card_number"),
+                fields: CARD_FIELDS,
+            },
+            EntryKind::Identity => KindSchema {
+                name: "title",
+                identifier: Some("full_name"),
+                secret: None,
+                fields: IDENTITY_FIELDS,
+            },
+            EntryKind::ApiToken => KindSchema {
+                name: "service",
+                identifier: Some("username_email"),
+                secret: Some("token"),
+                fields: API_TOKEN_FIELDS,
+            },
+            EntryKind::SshKey => KindSchema {
+                name: "title",
+                identifier: Some("public_key"),
+                secret: Some("private_key"),
+                fields: SSH_KEY_FIELDS,
+            },
+            EntryKind::WifiNetwork => KindSchema {
+                name: "ssid",
+                identifier: None,
+                secret: Some("password"),
+                fields: WIFI_NETWORK_FIELDS,
+            },
+        }
+    }
+}
+
+/// Checks an expiry date typed as `MM/YY`.
+pub fn is_valid_expiry_date(input: &str) -> bool {
+    match input.trim().split_once('/') {
+        Some((month, year)) => {
+            month.len() == 2
+                && year.len() == 2
+                && year.chars().all(|c| c.is_ascii_digit())
+                && matches!(month.parse::<u32>(), Ok(1..=12))
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expiry_date() {
+        assert!(is_valid_expiry_date("04/27"));
+        assert!(is_valid_expiry_date(" 12/30 "));
+        assert!(!is_valid_expiry_date("13/27"));
+        assert!(!is_valid_expiry_date("4/27"));
+        assert!(!is_valid_expiry_date("04/2027"));
+        assert!(!is_valid_expiry_date("0427"));
+    }
+
+    #[test]
+    fn test_field_keys_are_unique() {
+        for kind in EntryKind::ALL {
+            let schema = kind.schema();
+            let mut keys: Vec<&str> = schema.fields.iter().map(|field| field.key).collect();
+            keys.extend(schema.identifier);
+            keys.extend(schema.secret);
+
+            let count = keys.len();
+            keys.sort();
+            keys.dedup();
+
+            assert_eq!(keys.len(), count, "{:?}", kind);
+        }
+    }
+}
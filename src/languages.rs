@@ -1,6 +1,5 @@
 use std::collections::HashMap;
 use std::error::Error;
-use std::fs;
 
 use dialoguer::{theme::ColorfulTheme, Select};
 
@@ -19,9 +18,10 @@ pub struct Language<'a> {
 pub fn read_locales() {
     let mut translations = TRANSLATIONS.lock().unwrap();
 
-    let content = fs::read_to_string("locales.json").expect("Failed to read the file");
+    // Embedded so that NeoPass can be run from any directory.
+    let content = include_str!("../locales.json");
     let res: HashMap<String, HashMap<String, String>> =
-        serde_json::from_str(&content).expect("Cannot parse locale file");
+        serde_json::from_str(content).expect("Cannot parse locale file");
     translations.extend(res);
 }
 
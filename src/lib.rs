@@ -1,7 +1,27 @@
+pub mod attachments;
+pub mod audit;
+pub mod backups;
+pub mod breaches;
+pub mod cli;
 pub mod config;
+pub mod derived;
 pub mod entry;
+pub mod generator;
+pub mod inject;
+pub mod kinds;
 pub mod languages;
+pub mod lock;
+pub mod merge;
+pub mod migrations;
+pub mod otp;
+pub mod output;
 pub mod paging;
+pub mod passphrase;
 pub mod render;
+pub mod run;
 pub mod select;
+pub mod strength;
+pub mod trash;
 pub mod utils;
+pub mod vault;
+pub mod vaults;
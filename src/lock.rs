@@ -0,0 +1,106 @@
+use fs2::FileExt;
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::hash::Hasher;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::config::vault_sibling_path;
+
+#[derive(Debug, PartialEq)]
+pub enum VaultLockError {
+    // Another instance of NeoPass has the vault open.
+    ReadOnly,
+    // The vault file changed since NeoPass last read or wrote it.
+    ModifiedExternally,
+}
+
+impl fmt::Display for VaultLockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VaultLockError::ReadOnly => {
+                write!(f, "The vault is open in another instance of NeoPass")
+            }
+            VaultLockError::ModifiedExternally => {
+                write!(f, "The vault was modified by another program")
+            }
+        }
+    }
+}
+
+impl Error for VaultLockError {}
+
+lazy_static::lazy_static! {
+    // The lock is held as long as this file stays open.
+    static ref VAULT_LOCK: Mutex<Option<File>> = Mutex::new(None);
+    static ref VAULT_FINGERPRINT: Mutex<Option<u64>> = Mutex::new(None);
+}
+
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Takes an exclusive lock on the vault currently selected. When another instance
+/// already holds it, the vault is opened read-only.
+pub fn lock_vault() -> Result<(), Box<dyn Error>> {
+    unlock_vault();
+
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(vault_sibling_path(".lock"))?;
+
+    if file.try_lock_exclusive().is_ok() {
+        *VAULT_LOCK.lock().unwrap() = Some(file);
+        READ_ONLY.store(false, Ordering::SeqCst);
+    } else {
+        READ_ONLY.store(true, Ordering::SeqCst);
+    }
+
+    Ok(())
+}
+
+pub fn unlock_vault() {
+    if let Some(file) = VAULT_LOCK.lock().unwrap().take() {
+        let _ = FileExt::unlock(&file);
+    }
+    *VAULT_FINGERPRINT.lock().unwrap() = None;
+    READ_ONLY.store(false, Ordering::SeqCst);
+}
+
+pub fn is_read_only() -> bool {
+    READ_ONLY.load(Ordering::SeqCst)
+}
+
+pub fn fingerprint(path: &Path) -> Result<u64, Box<dyn Error>> {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(&fs::read(path)?);
+
+    Ok(hasher.finish())
+}
+
+/// Remembers the content of the vault as NeoPass last saw it.
+pub fn remember_vault_state(path: &Path) -> Result<(), Box<dyn Error>> {
+    *VAULT_FINGERPRINT.lock().unwrap() = Some(fingerprint(path)?);
+
+    Ok(())
+}
+
+/// Makes sure saving the vault will not drop changes made by someone else.
+pub fn check_vault_is_writable(path: &Path) -> Result<(), Box<dyn Error>> {
+    if is_read_only() {
+        return Err(Box::new(VaultLockError::ReadOnly));
+    }
+
+    let expected = *VAULT_FINGERPRINT.lock().unwrap();
+
+    if let Some(expected) = expected {
+        if path.exists() && fingerprint(path)? != expected {
+            return Err(Box::new(VaultLockError::ModifiedExternally));
+        }
+    }
+
+    Ok(())
+}
@@ -1,20 +1,51 @@
+use clap::Parser;
 use console::style;
 use dialoguer::theme::ColorfulTheme;
-use neopass::config::{read_local_config, INACTIVITY_DELAY};
-use neopass::entry::{add_a_new_entry, modify_entry};
+use neopass::attachments::manage_attachments;
+use neopass::audit::display_audit;
+use neopass::backups::restore_backup;
+use neopass::breaches::check_breaches;
+use neopass::cli::{exit_code, run_command, Cli};
+use neopass::config::{inactivity_delay, read_local_config, select_vault, CONFIG};
+use neopass::entry::{
+    add_a_new_entry, display_entry_details, display_password_history, modify_entry, Entry,
+};
 use neopass::languages::{read_locales, select_language};
+use neopass::lock::is_read_only;
+use neopass::otp::{Otp, OtpKind};
 use neopass::select::{Select, SelectOutput};
+use neopass::trash::{confirm_deletion, manage_trash, move_to_trash, visible_entries};
 use neopass::utils::{
-    add_first_entry, build_rows, change_master_password, clear_screen, display_instructions,
-    display_password_change, get_user_password, set_password_in_clipboard, write_entries_in_file,
+    add_first_entry, build_rows, change_master_password, clear_screen, copy_one_time_code,
+    display_instructions, display_password_change, display_read_only_warning, get_user_password,
+    mark_entry_as_used, save_entries, set_password_in_clipboard,
 };
+use neopass::vaults::switch_vault;
 use std::error::Error;
+use std::process;
 use std::time::{Duration, Instant};
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+
     read_local_config()?;
     read_locales();
+    let source = cli.password_source();
+    select_vault(cli.vault)?;
 
+    match cli.command {
+        Some(command) => {
+            if let Err(error) = run_command(command, source) {
+                eprintln!("{}", error);
+                process::exit(exit_code(error.as_ref()));
+            }
+            Ok(())
+        }
+        None => run_interactive(),
+    }
+}
+
+fn run_interactive() -> Result<(), Box<dyn Error>> {
     let mut password = String::new();
     let mut entries = Vec::new();
 
@@ -22,11 +53,29 @@ fn main() -> Result<(), Box<dyn Error>> {
     get_user_password(&mut entries, &mut password)?;
 
     let mut copied_item = None;
+    let mut copied_code = None;
+    // Entry the cursor jumps to, e.g. from the security audit.
+    let mut focused_item = None;
     let mut has_changed_master_password = false;
+    let mut has_tried_to_modify_vault = false;
+    // Versions of the entries before each change made during this session.
+    let mut undo_stack: Vec<Vec<Entry>> = Vec::new();
     let mut _last_activity = Instant::now();
+    // Reading the list of breached passwords is slow, it is only done when the
+    // entries change.
+    let mut breaches = check_breaches(&entries);
+    let mut checked_entries = entries.clone();
 
     loop {
-        if entries.is_empty() {
+        if entries != checked_entries {
+            breaches = check_breaches(&entries);
+            checked_entries = entries.clone();
+        }
+
+        let visible = visible_entries(&entries);
+
+        // Once entries are trashed, the empty table still gives access to the trash.
+        if entries.is_empty() && !is_read_only() {
             add_first_entry(&mut entries, &mut password)?;
             _last_activity = Instant::now();
             clear_screen()?;
@@ -34,10 +83,15 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
 
         display_password_change(&mut has_changed_master_password);
+        display_read_only_warning(&mut has_tried_to_modify_vault);
 
         display_instructions();
 
-        let (rows, header, footer) = build_rows(&entries, &copied_item);
+        // Passwords found in breaches are flagged. The audit screen shows why the list
+        // cannot be read, if so.
+        let breached = breaches.clone().unwrap_or_default();
+        let (rows, header, footer) =
+            build_rows(&entries, &visible, &breached, &copied_item, &copied_code);
 
         let theme = ColorfulTheme {
             header: style(header).for_stderr(),
@@ -50,43 +104,136 @@ fn main() -> Result<(), Box<dyn Error>> {
 
         // Display entries.
         if let Some(selection) = Select::with_theme(&theme)
-            .default(copied_item.unwrap_or_default())
+            .default(
+                copied_item
+                    .or(focused_item)
+                    .and_then(|index| visible.iter().position(|v| *v == index))
+                    .unwrap_or_default(),
+            )
             .items(&rows)
             .interact_opt()?
         {
             copied_item = None;
+            copied_code = None;
+            focused_item = None;
+
+            let selection = selection.map_index(|row| visible[row]);
 
-            // Check if INACTIVITY_DELAY seconds have elapsed since the last activity
-            if _last_activity.elapsed() >= Duration::from_secs(INACTIVITY_DELAY) {
+            // Check if the inactivity delay has elapsed since the last activity
+            if _last_activity.elapsed() >= Duration::from_secs(inactivity_delay()) {
                 clear_screen()?;
                 get_user_password(&mut entries, &mut password)?;
                 _last_activity = Instant::now();
             }
 
+            // Another instance of NeoPass is the only one allowed to write this vault.
+            if is_read_only() && selection.modifies_vault() {
+                has_tried_to_modify_vault = true;
+                clear_screen()?;
+                continue;
+            }
+
+            let is_undoable = matches!(
+                selection,
+                SelectOutput::Add
+                    | SelectOutput::Delete(_)
+                    | SelectOutput::Edit(_)
+                    | SelectOutput::RestoreBackup
+                    | SelectOutput::Trash
+            );
+            let previous_entries = entries.clone();
+
             match selection {
                 // User selected one item.
                 SelectOutput::Copy(index) => {
-                    set_password_in_clipboard(&entries, index, &mut copied_item)?;
+                    set_password_in_clipboard(&entries, index, &password, &mut copied_item)?;
+                    mark_entry_as_used(&mut entries, index, &password)?;
+                }
+
+                // User wants the one-time code of one item.
+                SelectOutput::CopyCode(index) => {
+                    let is_hotp = matches!(
+                        entries[index].otp,
+                        Some(Otp {
+                            kind: OtpKind::Hotp { .. },
+                            ..
+                        })
+                    );
+
+                    // HOTP counters have to be saved, which another instance may prevent.
+                    if is_read_only() && is_hotp {
+                        has_tried_to_modify_vault = true;
+                    } else {
+                        if copy_one_time_code(&mut entries, index, &mut copied_code)? {
+                            save_entries(&mut entries, &password)?;
+                        }
+                        copied_item = Some(index);
+                    }
                 }
 
                 // User wants to add a new item.
                 SelectOutput::Add => {
                     clear_screen()?;
                     add_a_new_entry(&mut entries);
-                    write_entries_in_file(&entries, &password)?;
+                    save_entries(&mut entries, &password)?;
                 }
 
                 // User wants to delete an item.
                 SelectOutput::Delete(index) => {
-                    let _removed_instance = entries.remove(index);
-                    write_entries_in_file(&entries, &password)?;
+                    if confirm_deletion(&entries[index])? {
+                        move_to_trash(&mut entries, index);
+                        save_entries(&mut entries, &password)?;
+                    }
+                }
+
+                // User wants to see the deleted items.
+                SelectOutput::Trash => {
+                    clear_screen()?;
+                    if manage_trash(&mut entries)? {
+                        save_entries(&mut entries, &password)?;
+                    }
+                }
+
+                // User wants to cancel their last change.
+                SelectOutput::Undo => {
+                    if let Some(previous) = undo_stack.pop() {
+                        entries = previous;
+                        save_entries(&mut entries, &password)?;
+                    }
                 }
 
                 // User wants to modify one item.
                 SelectOutput::Edit(index) => {
                     clear_screen()?;
                     modify_entry(&mut entries, index);
-                    write_entries_in_file(&entries, &password)?;
+                    save_entries(&mut entries, &password)?;
+                }
+
+                // User wants to see the details of one item.
+                SelectOutput::Details(index) => {
+                    clear_screen()?;
+                    display_entry_details(&entries[index])?;
+                }
+
+                // User wants to manage the files attached to one item.
+                SelectOutput::Attachments(index) => {
+                    clear_screen()?;
+                    if manage_attachments(&mut entries, index)? {
+                        save_entries(&mut entries, &password)?;
+                    }
+                }
+
+                // User wants to see the previous passwords of one item.
+                SelectOutput::PasswordHistory(index) => {
+                    clear_screen()?;
+                    display_password_history(&entries[index])?;
+                }
+
+                // User wants to check the health of the vault.
+                SelectOutput::Audit => {
+                    clear_screen()?;
+                    let rotation_days = CONFIG.lock().unwrap().password_rotation_days;
+                    focused_item = display_audit(&entries, &breaches, rotation_days)?;
                 }
 
                 // User wants to change the language.
@@ -98,9 +245,26 @@ fn main() -> Result<(), Box<dyn Error>> {
                 // User wants to change the master password.
                 SelectOutput::ChangeMasterPassword => {
                     clear_screen()?;
-                    change_master_password(&mut entries, &mut password)?;
-                    has_changed_master_password = true;
+                    has_changed_master_password =
+                        change_master_password(&mut entries, &mut password)?;
+                }
+
+                // User wants to restore a backup of the vault.
+                SelectOutput::RestoreBackup => {
+                    clear_screen()?;
+                    restore_backup(&mut entries, &password)?;
                 }
+
+                // User wants to open another vault.
+                SelectOutput::SwitchVault => {
+                    clear_screen()?;
+                    switch_vault(&mut entries, &mut password)?;
+                    undo_stack.clear();
+                }
+            }
+
+            if is_undoable && entries != previous_entries {
+                undo_stack.push(previous_entries);
             }
         } else {
             clear_screen()?;
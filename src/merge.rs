@@ -0,0 +1,315 @@
+use dialoguer::{theme::ColorfulTheme, Confirm, Select};
+use std::error::Error;
+use std::path::Path;
+
+use crate::entry::Entry;
+use crate::languages::get_translation;
+use crate::utils::{unlock_vault_file, write_entries_in_file};
+
+/// Both vaults changed the same entry differently since their common base.
+/// `None` means the entry was deleted on that side.
+#[derive(Debug, PartialEq)]
+pub struct Conflict {
+    pub ours: Option<Entry>,
+    pub theirs: Option<Entry>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Merge {
+    pub entries: Vec<Entry>,
+    pub conflicts: Vec<Conflict>,
+}
+
+fn find<'a>(entries: &'a [Entry], entry: &Entry) -> Option<&'a Entry> {
+    entries.iter().find(|e| e.is_same_account(entry))
+}
+
+fn same_content(a: Option<&Entry>, b: Option<&Entry>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.has_same_content(b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+// Without a base, the most recently modified version wins when both dates are known.
+fn most_recent<'a>(ours: &'a Entry, theirs: &'a Entry) -> Option<&'a Entry> {
+    match (ours.modified_at, theirs.modified_at) {
+        (Some(a), Some(b)) if a > b => Some(ours),
+        (Some(a), Some(b)) if a < b => Some(theirs),
+        _ => None,
+    }
+}
+
+/// Three-way merge of two copies of a vault.
+///
+/// Without a base, an entry present in both vaults with different values is a conflict,
+/// unless its modification dates tell which version is the most recent.
+pub fn merge_entries(base: Option<&[Entry]>, ours: &[Entry], theirs: &[Entry]) -> Merge {
+    let has_base = base.is_some();
+    let base = base.unwrap_or_default();
+    let mut merge = Merge::default();
+
+    // Our entries first, so the merged vault keeps our order.
+    let mut accounts: Vec<&Entry> = ours.iter().collect();
+    for entry in theirs.iter().chain(base) {
+        if !accounts.iter().any(|e| e.is_same_account(entry)) {
+            accounts.push(entry);
+        }
+    }
+
+    for account in accounts {
+        let ancestor = find(base, account);
+        let ours = find(ours, account);
+        let theirs = find(theirs, account);
+
+        let merged = if same_content(ours, theirs) || same_content(theirs, ancestor) {
+            ours
+        } else if same_content(ours, ancestor) {
+            theirs
+        } else if let Some(entry) = ours
+            .zip(theirs)
+            .filter(|_| !has_base)
+            .and_then(|(ours, theirs)| most_recent(ours, theirs))
+        {
+            Some(entry)
+        } else {
+            merge.conflicts.push(Conflict {
+                ours: ours.cloned(),
+                theirs: theirs.cloned(),
+            });
+            continue;
+        };
+
+        if let Some(entry) = merged {
+            let mut entry = entry.clone();
+            // Keep the last use recorded on either side.
+            entry.last_used_at = [ours, theirs]
+                .into_iter()
+                .flatten()
+                .filter_map(|e| e.last_used_at)
+                .max();
+            merge.entries.push(entry);
+        }
+    }
+
+    merge
+}
+
+fn describe(entry: &Option<Entry>, side: &str) -> String {
+    match entry {
+        Some(entry) => format!(
+            "{} {} ({})",
+            get_translation(side),
+            entry.application,
+            entry.username
+        ),
+        None => format!(
+            "{} {}",
+            get_translation(side),
+            get_translation("merge_deleted")
+        ),
+    }
+}
+
+/// Lets the user pick the version to keep for every conflict.
+pub fn resolve_conflicts(merge: &mut Merge) -> Result<bool, Box<dyn Error>> {
+    let conflicts = std::mem::take(&mut merge.conflicts);
+    let total = conflicts.len();
+
+    for (index, conflict) in conflicts.into_iter().enumerate() {
+        let account = conflict.ours.as_ref().or(conflict.theirs.as_ref()).unwrap();
+
+        println!(
+            "\n  {} {}/{}: {} ({})",
+            get_translation("merge_conflict"),
+            index + 1,
+            total,
+            account.application,
+            account.username
+        );
+
+        let mut items = vec![
+            describe(&conflict.ours, "merge_keep_ours"),
+            describe(&conflict.theirs, "merge_keep_theirs"),
+        ];
+        if conflict.ours.is_some() && conflict.theirs.is_some() {
+            items.push(get_translation("merge_keep_both"));
+        }
+
+        let selection = match Select::with_theme(&ColorfulTheme::default())
+            .default(0)
+            .items(&items)
+            .interact_opt()?
+        {
+            Some(selection) => selection,
+            None => return Ok(false),
+        };
+
+        match selection {
+            0 => merge.entries.extend(conflict.ours),
+            1 => merge.entries.extend(conflict.theirs),
+            _ => merge
+                .entries
+                .extend(conflict.ours.into_iter().chain(conflict.theirs)),
+        }
+    }
+
+    Ok(true)
+}
+
+/// Merges another copy of the vault, and optionally their common ancestor, into `entries`.
+pub fn merge_vault_file(
+    entries: &[Entry],
+    password: &str,
+    other: &Path,
+    base: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    let theirs = unlock_vault_file(other, password, &get_translation("enter_other_password"))?;
+    let base = match base {
+        Some(base) => Some(unlock_vault_file(
+            base,
+            password,
+            &get_translation("enter_base_password"),
+        )?),
+        None => None,
+    };
+
+    let mut merge = merge_entries(base.as_deref(), entries, &theirs);
+
+    println!(
+        "\n  {} {}  ·  {} {}",
+        merge.entries.len(),
+        get_translation("entries"),
+        merge.conflicts.len(),
+        get_translation("merge_conflicts")
+    );
+
+    if !resolve_conflicts(&mut merge)? {
+        return Ok(());
+    }
+
+    if merge.entries == entries {
+        println!("\n  {}", get_translation("merge_nothing_to_do"));
+        return Ok(());
+    }
+
+    if Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("  {}", get_translation("confirm_merge")))
+        .default(true)
+        .interact()?
+    {
+        write_entries_in_file(&merge.entries, password)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    fn entry(application: &str, password: &str) -> Entry {
+        Entry {
+            id: Uuid::new_v5(&Uuid::NAMESPACE_OID, application.as_bytes()),
+            password_changed_at: None,
+            created_at: None,
+            modified_at: None,
+            ..Entry::new(
+                application.to_string(),
+                "john".to_string(),
+                password.to_string(),
+            )
+        }
+    }
+
+    #[test]
+    fn test_changes_on_one_side_are_applied() {
+        let base = vec![entry("Mail", "1"), entry("Bank", "1"), entry("Forum", "1")];
+        let ours = vec![entry("Mail", "2"), entry("Bank", "1"), entry("Forum", "1")];
+        let theirs = vec![entry("Mail", "1"), entry("Bank", "1"), entry("Shop", "1")];
+
+        let merge = merge_entries(Some(&base), &ours, &theirs);
+
+        assert_eq!(
+            merge.entries,
+            vec![entry("Mail", "2"), entry("Bank", "1"), entry("Shop", "1")]
+        );
+        assert!(merge.conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_same_change_on_both_sides() {
+        let base = vec![entry("Mail", "1")];
+        let ours = vec![entry("Mail", "2")];
+
+        let merge = merge_entries(Some(&base), &ours, &ours);
+
+        assert_eq!(merge.entries, ours);
+        assert!(merge.conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let base = vec![entry("Mail", "1"), entry("Bank", "1")];
+        let ours = vec![entry("Mail", "2"), entry("Bank", "2")];
+        let theirs = vec![entry("Mail", "3")];
+
+        let merge = merge_entries(Some(&base), &ours, &theirs);
+
+        assert!(merge.entries.is_empty());
+        assert_eq!(
+            merge.conflicts,
+            vec![
+                Conflict {
+                    ours: Some(entry("Mail", "2")),
+                    theirs: Some(entry("Mail", "3")),
+                },
+                Conflict {
+                    ours: Some(entry("Bank", "2")),
+                    theirs: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_without_base() {
+        let ours = vec![entry("Mail", "1"), entry("Bank", "1")];
+        let theirs = vec![entry("Mail", "2"), entry("Shop", "1")];
+
+        let merge = merge_entries(None, &ours, &theirs);
+
+        assert_eq!(merge.entries, vec![entry("Bank", "1"), entry("Shop", "1")]);
+        assert_eq!(merge.conflicts.len(), 1);
+    }
+
+    #[test]
+    fn test_without_base_most_recent_wins() {
+        let now = Utc::now();
+        let mut ours = entry("Mail", "1");
+        ours.modified_at = Some(now);
+        let mut theirs = entry("Mail", "2");
+        theirs.modified_at = Some(now + Duration::minutes(1));
+
+        let merge = merge_entries(None, &[ours], &[theirs.clone()]);
+
+        assert_eq!(merge.entries, vec![theirs]);
+        assert!(merge.conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_last_use_is_kept() {
+        let base = vec![entry("Mail", "1")];
+        let mut ours = base.clone();
+        ours[0].last_used_at = Some(Utc::now());
+        let theirs = vec![entry("Mail", "2")];
+
+        let merge = merge_entries(Some(&base), &ours, &theirs);
+
+        assert_eq!(merge.entries[0].password, "2");
+        assert_eq!(merge.entries[0].last_used_at, ours[0].last_used_at);
+    }
+}
@@ -0,0 +1,305 @@
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{Error as IoError, ErrorKind};
+use uuid::Uuid;
+
+use crate::vault::VAULT_VERSION;
+
+// Content written in the vault by the first versions of NeoPass when it was created.
+const LEGACY_EMPTY_VAULT: &str = "BEGIN";
+
+// Namespace of the identifiers given to entries that were created without one.
+const LEGACY_ENTRY_NAMESPACE: Uuid = Uuid::from_u128(0x3c1f_6b0e_8d4a_4f7e_9a52_1e6d_0b7c_a914);
+
+type Migration = fn(Value) -> Result<Value, Box<dyn Error>>;
+
+// `MIGRATIONS[n]` upgrades a vault document from version `n` to version `n + 1`.
+// Bumping `VAULT_VERSION` without adding its step here does not compile.
+const MIGRATIONS: [Migration; VAULT_VERSION as usize] = [
+    migrate_v0_to_v1,
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+    migrate_v3_to_v4,
+    migrate_v4_to_v5,
+    migrate_v5_to_v6,
+    migrate_v6_to_v7,
+    migrate_v7_to_v8,
+    migrate_v8_to_v9,
+    migrate_v9_to_v10,
+    migrate_v10_to_v11,
+];
+
+/// Wraps a payload written before vaults were versioned into a version 0 document.
+pub fn legacy_document(payload: &str) -> Value {
+    json!({ "version": 0, "payload": payload })
+}
+
+pub fn document_version(document: &Value) -> Result<u32, Box<dyn Error>> {
+    match document.get("version").and_then(Value::as_u64) {
+        Some(version) => Ok(version as u32),
+        None => Err(invalid_document("The vault has no version number")),
+    }
+}
+
+/// Applies every migration step needed to bring `document` to `VAULT_VERSION`.
+pub fn migrate(mut document: Value) -> Result<Value, Box<dyn Error>> {
+    let mut version = document_version(&document)?;
+
+    if version > VAULT_VERSION {
+        return Err(invalid_document(
+            "This vault was created by a more recent version of NeoPass",
+        ));
+    }
+
+    while version < VAULT_VERSION {
+        document = MIGRATIONS[version as usize](document)?;
+        version += 1;
+        document["version"] = json!(version);
+    }
+
+    Ok(document)
+}
+
+// Version 0 is the `application,username,password` line format.
+fn migrate_v0_to_v1(document: Value) -> Result<Value, Box<dyn Error>> {
+    let payload = match document.get("payload").and_then(Value::as_str) {
+        Some(payload) => payload,
+        None => return Err(invalid_document("The legacy vault has no payload")),
+    };
+
+    let mut entries = Vec::new();
+
+    for line in payload.split('\n') {
+        if line.trim() == LEGACY_EMPTY_VAULT {
+            continue;
+        }
+
+        let parts: Vec<&str> = line.split(',').collect();
+        if parts.len() == 3 {
+            let mut entry = Map::new();
+            entry.insert("application".to_string(), json!(parts[0].trim()));
+            entry.insert("username".to_string(), json!(parts[1].trim()));
+            entry.insert("password".to_string(), json!(parts[2].trim()));
+            entries.push(Value::Object(entry));
+        }
+    }
+
+    Ok(json!({ "version": 1, "entries": entries }))
+}
+
+// Version 2 gives every entry an identifier. It is derived from the account, so two
+// copies of the same vault upgraded separately still agree on it. When it was created,
+// modified or last used is unknown.
+fn migrate_v1_to_v2(mut document: Value) -> Result<Value, Box<dyn Error>> {
+    let entries = match document.get_mut("entries").and_then(Value::as_array_mut) {
+        Some(entries) => entries,
+        None => return Err(invalid_document("The vault has no entries")),
+    };
+
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+
+    for entry in entries {
+        let account = format!(
+            "{}\n{}",
+            entry["application"].as_str().unwrap_or_default(),
+            entry["username"].as_str().unwrap_or_default()
+        );
+
+        // Duplicated accounts still need distinct identifiers.
+        let occurrence = occurrences.entry(account.clone()).or_default();
+        let name = match *occurrence {
+            0 => account,
+            n => format!("{}#{}", account, n),
+        };
+        *occurrence += 1;
+
+        entry["id"] = json!(Uuid::new_v5(&LEGACY_ENTRY_NAMESPACE, name.as_bytes()));
+    }
+
+    Ok(document)
+}
+
+// Version 3 adds URLs, notes, tags and custom fields to entries. They are all optional,
+// but older versions of NeoPass would drop them when saving the vault.
+fn migrate_v2_to_v3(document: Value) -> Result<Value, Box<dyn Error>> {
+    Ok(document)
+}
+
+// Version 4 adds one-time password generators to entries.
+fn migrate_v3_to_v4(document: Value) -> Result<Value, Box<dyn Error>> {
+    Ok(document)
+}
+
+// Version 5 adds kinds of entries, such as payment cards or SSH keys. Entries without
+// a kind are logins.
+fn migrate_v4_to_v5(document: Value) -> Result<Value, Box<dyn Error>> {
+    Ok(document)
+}
+
+// Version 6 adds attachments to entries.
+fn migrate_v5_to_v6(document: Value) -> Result<Value, Box<dyn Error>> {
+    Ok(document)
+}
+
+// Version 7 keeps the previous passwords of entries.
+fn migrate_v6_to_v7(document: Value) -> Result<Value, Box<dyn Error>> {
+    Ok(document)
+}
+
+// Version 8 keeps deleted entries in a trash.
+fn migrate_v7_to_v8(document: Value) -> Result<Value, Box<dyn Error>> {
+    Ok(document)
+}
+
+// Version 9 lets entries have their own password generation rules.
+fn migrate_v8_to_v9(document: Value) -> Result<Value, Box<dyn Error>> {
+    Ok(document)
+}
+
+// Version 10 lets entries derive their password instead of storing it.
+fn migrate_v9_to_v10(document: Value) -> Result<Value, Box<dyn Error>> {
+    Ok(document)
+}
+
+// Version 11 keeps when the password of entries was set. Before, it was when the last
+// one was replaced, or when the entry was created.
+fn migrate_v10_to_v11(mut document: Value) -> Result<Value, Box<dyn Error>> {
+    let entries = match document.get_mut("entries").and_then(Value::as_array_mut) {
+        Some(entries) => entries,
+        None => return Err(invalid_document("The vault has no entries")),
+    };
+
+    for entry in entries {
+        let changed_at = match entry.pointer("/password_history/0/replaced_at") {
+            Some(replaced_at) => replaced_at.clone(),
+            None => entry.get("created_at").cloned().unwrap_or(Value::Null),
+        };
+
+        if !changed_at.is_null() {
+            entry["password_changed_at"] = changed_at;
+        }
+    }
+
+    Ok(document)
+}
+
+fn invalid_document(message: &str) -> Box<dyn Error> {
+    Box::new(IoError::new(ErrorKind::InvalidData, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_v0_to_v1() {
+        let document = legacy_document("Mail, john@doe.com ,secret\nbroken line\n");
+
+        assert_eq!(
+            migrate_v0_to_v1(document).unwrap(),
+            json!({
+                "version": 1,
+                "entries": [
+                    { "application": "Mail", "username": "john@doe.com", "password": "secret" }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_v0_to_v1_empty_vault() {
+        let document = migrate_v0_to_v1(legacy_document("BEGIN")).unwrap();
+
+        assert_eq!(document["entries"], json!([]));
+    }
+
+    #[test]
+    fn test_v1_to_v2() {
+        let entry = json!({ "application": "Mail", "username": "john", "password": "secret" });
+        let document = json!({ "version": 1, "entries": [entry, entry, { "application": "Bank", "username": "john", "password": "secret" }] });
+
+        let first = migrate_v1_to_v2(document.clone()).unwrap();
+        let second = migrate_v1_to_v2(document).unwrap();
+
+        let ids: Vec<&Value> = first["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| &entry["id"])
+            .collect();
+
+        assert!(ids.iter().all(|id| id.is_string()));
+        assert_ne!(ids[0], ids[1]);
+        assert_ne!(ids[0], ids[2]);
+        assert_eq!(first, second);
+        assert_eq!(first["entries"][0]["password"], "secret");
+    }
+
+    // Versions 3 to 10 only added fields, their steps keep documents as they are.
+    #[test]
+    fn test_steps_adding_fields() {
+        for version in 2..10 {
+            let document = json!({ "version": version, "entries": [
+                { "id": Uuid::nil(), "application": "Mail", "username": "john", "password": "secret" }
+            ] });
+
+            assert_eq!(
+                MIGRATIONS[version as usize](document.clone()).unwrap(),
+                document,
+                "step from version {}",
+                version
+            );
+        }
+    }
+
+    #[test]
+    fn test_v10_to_v11() {
+        let document = json!({ "version": 10, "entries": [
+            {
+                "application": "Mail",
+                "password_history": [
+                    { "password": "2", "replaced_at": "2024-05-01T00:00:00Z" },
+                    { "password": "1", "replaced_at": "2023-05-01T00:00:00Z" }
+                ],
+                "created_at": "2022-05-01T00:00:00Z"
+            },
+            { "application": "Bank", "created_at": "2022-05-01T00:00:00Z" },
+            { "application": "Forum" }
+        ] });
+
+        let document = migrate_v10_to_v11(document).unwrap();
+        let entries = &document["entries"];
+
+        assert_eq!(entries[0]["password_changed_at"], "2024-05-01T00:00:00Z");
+        assert_eq!(entries[1]["password_changed_at"], "2022-05-01T00:00:00Z");
+        assert!(entries[2].get("password_changed_at").is_none());
+    }
+
+    #[test]
+    fn test_migrate_runs_every_step() {
+        let document = migrate(legacy_document("Mail,john,secret")).unwrap();
+
+        assert_eq!(document_version(&document).unwrap(), VAULT_VERSION);
+        assert_eq!(document["entries"][0]["application"], "Mail");
+    }
+
+    #[test]
+    fn test_migrate_keeps_current_document() {
+        let document = json!({ "version": VAULT_VERSION, "entries": [] });
+
+        assert_eq!(migrate(document.clone()).unwrap(), document);
+    }
+
+    #[test]
+    fn test_migrate_refuses_newer_document() {
+        let document = json!({ "version": VAULT_VERSION + 1, "entries": [] });
+
+        assert!(migrate(document).is_err());
+    }
+
+    #[test]
+    fn test_missing_version() {
+        assert!(migrate(json!({ "entries": [] })).is_err());
+    }
+}
@@ -0,0 +1,330 @@
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use std::error::Error;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DEFAULT_DIGITS: u32 = 6;
+const DEFAULT_PERIOD: u64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Algorithm::Sha1 => write!(f, "SHA1"),
+            Algorithm::Sha256 => write!(f, "SHA256"),
+            Algorithm::Sha512 => write!(f, "SHA512"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OtpKind {
+    // RFC 6238, the code changes every `period` seconds.
+    Totp { period: u64 },
+    // RFC 4226, the code changes every time one is generated.
+    Hotp { counter: u64 },
+}
+
+/// Secret of a one-time password generator, as found in `otpauth://` URIs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Otp {
+    // Base32, as shown by the websites enabling two-factor authentication.
+    pub secret: String,
+    pub algorithm: Algorithm,
+    pub digits: u32,
+    pub kind: OtpKind,
+}
+
+impl Otp {
+    /// Reads an `otpauth://` URI, or a bare base32 secret for the usual TOTP settings.
+    pub fn parse(input: &str) -> Result<Otp, Box<dyn Error>> {
+        let input = input.trim();
+
+        let (kind, query) = match input.strip_prefix("otpauth://") {
+            Some(rest) => {
+                let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+                let kind = path.split('/').next().unwrap_or_default();
+                (kind.to_lowercase(), query)
+            }
+            None => {
+                let otp = Otp {
+                    secret: normalize_secret(input),
+                    algorithm: Algorithm::Sha1,
+                    digits: DEFAULT_DIGITS,
+                    kind: OtpKind::Totp {
+                        period: DEFAULT_PERIOD,
+                    },
+                };
+                otp.key()?;
+                return Ok(otp);
+            }
+        };
+
+        let mut secret = None;
+        let mut algorithm = Algorithm::Sha1;
+        let mut digits = DEFAULT_DIGITS;
+        let mut period = DEFAULT_PERIOD;
+        let mut counter = 0;
+
+        for parameter in query.split('&') {
+            let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+
+            match name.to_lowercase().as_str() {
+                "secret" => secret = Some(normalize_secret(value)),
+                "algorithm" => {
+                    algorithm = match value.to_uppercase().as_str() {
+                        "SHA1" => Algorithm::Sha1,
+                        "SHA256" => Algorithm::Sha256,
+                        "SHA512" => Algorithm::Sha512,
+                        _ => return Err(format!("Unsupported algorithm {}", value).into()),
+                    }
+                }
+                "digits" => digits = value.parse()?,
+                "period" => period = value.parse()?,
+                "counter" => counter = value.parse()?,
+                _ => {}
+            }
+        }
+
+        let kind = match kind.as_str() {
+            "totp" if period > 0 => OtpKind::Totp { period },
+            "hotp" => OtpKind::Hotp { counter },
+            _ => return Err(format!("Unsupported one-time password {}", input).into()),
+        };
+
+        if !(6..=9).contains(&digits) {
+            return Err(format!("Unsupported number of digits {}", digits).into());
+        }
+
+        let otp = Otp {
+            secret: secret.ok_or("The URI has no secret")?,
+            algorithm,
+            digits,
+            kind,
+        };
+        otp.key()?;
+
+        Ok(otp)
+    }
+
+    pub fn to_uri(&self) -> String {
+        let (kind, parameter) = match self.kind {
+            OtpKind::Totp { period } => ("totp", format!("period={}", period)),
+            OtpKind::Hotp { counter } => ("hotp", format!("counter={}", counter)),
+        };
+
+        format!(
+            "otpauth://{}/NeoPass?secret={}&algorithm={}&digits={}&{}",
+            kind, self.secret, self.algorithm, self.digits, parameter
+        )
+    }
+
+    fn key(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        match BASE32_NOPAD.decode(self.secret.as_bytes()) {
+            Ok(key) if !key.is_empty() => Ok(key),
+            _ => Err("The one-time password secret is not valid base32".into()),
+        }
+    }
+
+    /// TOTP code at `time`, in seconds since the Unix epoch, and how many seconds it
+    /// stays valid. `None` for HOTP generators, which do not depend on time.
+    pub fn code_at(&self, time: u64) -> Option<(String, u64)> {
+        match self.kind {
+            OtpKind::Totp { period } => {
+                let code = hotp(
+                    &self.key().ok()?,
+                    time / period,
+                    self.digits,
+                    self.algorithm,
+                );
+                Some((code, period - time % period))
+            }
+            OtpKind::Hotp { .. } => None,
+        }
+    }
+
+    pub fn current_code(&self) -> Option<(String, u64)> {
+        self.code_at(now())
+    }
+
+    /// Next HOTP code. The counter moves forward, so the entry has to be saved.
+    pub fn next_code(&mut self) -> Option<String> {
+        let key = self.key().ok()?;
+
+        match &mut self.kind {
+            OtpKind::Hotp { counter } => {
+                let code = hotp(&key, *counter, self.digits, self.algorithm);
+                *counter += 1;
+                Some(code)
+            }
+            OtpKind::Totp { .. } => None,
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+// Secrets are often displayed lowercase, in groups of four, or padded.
+fn normalize_secret(secret: &str) -> String {
+    secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=' && *c != '-')
+        .collect::<String>()
+        .to_uppercase()
+}
+
+fn hmac<M: Mac + hmac::digest::KeyInit>(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac =
+        <M as hmac::digest::KeyInit>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// RFC 4226 code for `counter`.
+pub fn hotp(key: &[u8], counter: u64, digits: u32, algorithm: Algorithm) -> String {
+    let message = counter.to_be_bytes();
+
+    let hash = match algorithm {
+        Algorithm::Sha1 => hmac::<Hmac<Sha1>>(key, &message),
+        Algorithm::Sha256 => hmac::<Hmac<Sha256>>(key, &message),
+        Algorithm::Sha512 => hmac::<Hmac<Sha512>>(key, &message),
+    };
+
+    // Dynamic truncation.
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    format!(
+        "{:0width$}",
+        binary as u64 % 10u64.pow(digits),
+        width = digits as usize
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA1_SEED: &[u8] = b"12345678901234567890";
+    const SHA256_SEED: &[u8] = b"12345678901234567890123456789012";
+    const SHA512_SEED: &[u8] = b"1234567890123456789012345678901234567890123456789012345678901234";
+
+    fn totp(seed: &[u8], algorithm: Algorithm) -> Otp {
+        Otp {
+            secret: BASE32_NOPAD.encode(seed),
+            algorithm,
+            digits: 8,
+            kind: OtpKind::Totp { period: 30 },
+        }
+    }
+
+    #[test]
+    fn test_hotp_rfc_4226() {
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583",
+            "399871", "520489",
+        ];
+
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(hotp(SHA1_SEED, counter as u64, 6, Algorithm::Sha1), *code);
+        }
+    }
+
+    #[test]
+    fn test_totp_rfc_6238() {
+        let expected = [
+            (59, "94287082", "46119246", "90693936"),
+            (1111111109, "07081804", "68084774", "25091201"),
+            (1111111111, "14050471", "67062674", "99943326"),
+            (1234567890, "89005924", "91819424", "93441116"),
+            (2000000000, "69279037", "90698825", "38618901"),
+            (20000000000, "65353130", "77737706", "47863826"),
+        ];
+
+        for (time, sha1, sha256, sha512) in expected {
+            let code = |otp: Otp| otp.code_at(time).unwrap().0;
+
+            assert_eq!(code(totp(SHA1_SEED, Algorithm::Sha1)), sha1);
+            assert_eq!(code(totp(SHA256_SEED, Algorithm::Sha256)), sha256);
+            assert_eq!(code(totp(SHA512_SEED, Algorithm::Sha512)), sha512);
+        }
+    }
+
+    #[test]
+    fn test_totp_remaining_seconds() {
+        let otp = totp(SHA1_SEED, Algorithm::Sha1);
+
+        assert_eq!(otp.code_at(59).unwrap().1, 1);
+        assert_eq!(otp.code_at(60).unwrap().1, 30);
+    }
+
+    #[test]
+    fn test_hotp_counter_moves_forward() {
+        let mut otp = Otp::parse(&format!(
+            "otpauth://hotp/Mail:john?secret={}&counter=1",
+            BASE32_NOPAD.encode(SHA1_SEED)
+        ))
+        .unwrap();
+
+        assert_eq!(otp.next_code().unwrap(), "287082");
+        assert_eq!(otp.next_code().unwrap(), "359152");
+        assert_eq!(otp.kind, OtpKind::Hotp { counter: 3 });
+        assert_eq!(otp.code_at(59), None);
+    }
+
+    #[test]
+    fn test_parse_uri() {
+        let otp = Otp::parse(
+            "otpauth://totp/Mail:john?secret=gezdgnbvgy3tqojq&issuer=Mail&algorithm=SHA256&digits=8&period=60",
+        )
+        .unwrap();
+
+        assert_eq!(
+            otp,
+            Otp {
+                secret: "GEZDGNBVGY3TQOJQ".to_string(),
+                algorithm: Algorithm::Sha256,
+                digits: 8,
+                kind: OtpKind::Totp { period: 60 },
+            }
+        );
+        assert_eq!(Otp::parse(&otp.to_uri()).unwrap(), otp);
+    }
+
+    #[test]
+    fn test_parse_bare_secret() {
+        let otp = Otp::parse("gezd gnbv gy3t qojq").unwrap();
+
+        assert_eq!(otp.secret, "GEZDGNBVGY3TQOJQ");
+        assert_eq!(otp.kind, OtpKind::Totp { period: 30 });
+        assert_eq!(otp.digits, 6);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(Otp::parse("not base32!").is_err());
+        assert!(Otp::parse("otpauth://totp/Mail?digits=6").is_err());
+        assert!(Otp::parse("otpauth://totp/Mail?secret=GEZDGNBV&algorithm=MD5").is_err());
+    }
+}
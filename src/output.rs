@@ -0,0 +1,223 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::derived::entry_password;
+use crate::entry::Entry;
+
+/// An entry as printed by `neopass list --json` and `neopass get --json`.
+///
+/// This format is meant for scripts and is documented in the README: fields may be
+/// added, but never renamed or removed. It does not follow the format of the vault.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct EntryJson {
+    pub id: String,
+    pub kind: String,
+    pub name: String,
+    pub username: String,
+    // Only with `--reveal`. Derived passwords are computed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    // Concealed values, such as a PIN, only with `--reveal`.
+    pub fields: BTreeMap<String, String>,
+    pub urls: Vec<String>,
+    pub tags: Vec<String>,
+    pub notes: String,
+    pub custom_fields: Vec<CustomFieldJson>,
+    pub has_otp: bool,
+    pub derived: bool,
+    pub attachments: Vec<AttachmentJson>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub modified_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CustomFieldJson {
+    pub name: String,
+    // Only with `--reveal` when the field is concealed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    pub concealed: bool,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct AttachmentJson {
+    pub name: String,
+    pub size: u64,
+}
+
+fn kind_name(entry: &Entry) -> String {
+    serde_json::to_value(entry.kind)
+        .ok()
+        .and_then(|value| value.as_str().map(|kind| kind.to_string()))
+        .unwrap_or_default()
+}
+
+/// `master_password` is needed to reveal derived passwords.
+pub fn entry_json(
+    entry: &Entry,
+    master_password: &str,
+    reveal: bool,
+) -> Result<EntryJson, Box<dyn Error>> {
+    let schema = entry.kind.schema();
+
+    let password = match schema.secret {
+        Some(_) if reveal => Some(entry_password(entry, master_password)?),
+        _ => None,
+    };
+
+    let fields = schema
+        .fields
+        .iter()
+        .filter(|field| reveal || !field.concealed)
+        .filter_map(|field| Some((field.key.to_string(), entry.fields.get(field.key)?.clone())))
+        .collect();
+
+    Ok(EntryJson {
+        id: entry.id.to_string(),
+        kind: kind_name(entry),
+        name: entry.application.clone(),
+        username: entry.username.clone(),
+        password,
+        fields,
+        urls: entry.urls.clone(),
+        tags: entry.tags.iter().cloned().collect(),
+        notes: entry.notes.clone(),
+        custom_fields: entry
+            .custom_fields
+            .iter()
+            .map(|field| CustomFieldJson {
+                name: field.name.clone(),
+                value: (reveal || !field.concealed).then(|| field.value.clone()),
+                concealed: field.concealed,
+            })
+            .collect(),
+        has_otp: entry.otp.is_some(),
+        derived: entry.derivation.is_some(),
+        attachments: entry
+            .attachments
+            .iter()
+            .map(|attachment| AttachmentJson {
+                name: attachment.name.clone(),
+                size: attachment.size,
+            })
+            .collect(),
+        created_at: entry.created_at,
+        modified_at: entry.modified_at,
+        last_used_at: entry.last_used_at,
+    })
+}
+
+/// Value of one field of an entry, as selected with `--field`: `id`, `kind`, `name`,
+/// `username`, `password`, `notes`, `urls`, `tags`, `otp` (the current one-time code),
+/// a field of the kind of the entry (such as `cvv`) or the name of a custom field.
+/// The labels of the kind, such as `token` or `cardholder`, work too. Lists give one
+/// value per line.
+pub fn field_value(
+    entry: &Entry,
+    field: &str,
+    master_password: &str,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let schema = entry.kind.schema();
+
+    let value = match field {
+        "id" => entry.id.to_string(),
+        "kind" => kind_name(entry),
+        "name" => entry.application.clone(),
+        "username" => entry.username.clone(),
+        "password" => entry_password(entry, master_password)?,
+        "notes" => entry.notes.clone(),
+        "urls" => entry.urls.join("\n"),
+        "tags" => entry
+            .tags
+            .iter()
+            .cloned()
+            .collect::<Vec<String>>()
+            .join("\n"),
+        "otp" => match &entry.otp {
+            Some(otp) => match otp.current_code() {
+                Some((code, _)) => code,
+                // Counter-based codes change the vault, they are copied instead.
+                None => return Err("Only time-based one-time codes can be printed".into()),
+            },
+            None => return Ok(None),
+        },
+        _ if schema.identifier == Some(field) => entry.username.clone(),
+        _ if schema.secret == Some(field) => entry_password(entry, master_password)?,
+        _ => {
+            if let Some(value) = entry.fields.get(field) {
+                value.clone()
+            } else if let Some(custom) = entry.custom_fields.iter().find(|c| c.name == field) {
+                custom.value.clone()
+            } else {
+                return Ok(None);
+            }
+        }
+    };
+
+    Ok(Some(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::CustomField;
+    use crate::kinds::EntryKind;
+
+    fn card() -> Entry {
+        let mut entry = Entry::new(
+            "Visa".to_string(),
+            "John Smith".to_string(),
+            "4970100000000000".to_string(),
+        );
+        entry.kind = EntryKind::Card;
+        entry
+            .fields
+            .insert("expiry_date".to_string(), "12/30".to_string());
+        entry.fields.insert("cvv".to_string(), "123".to_string());
+        entry.custom_fields.push(CustomField {
+            name: "Bank PIN".to_string(),
+            value: "4321".to_string(),
+            concealed: true,
+        });
+        entry
+    }
+
+    #[test]
+    fn test_secrets_need_reveal() {
+        let masked = entry_json(&card(), "master", false).unwrap();
+        let revealed = entry_json(&card(), "master", true).unwrap();
+
+        assert_eq!(masked.kind, "card");
+        assert_eq!(masked.password, None);
+        assert_eq!(
+            masked.fields.keys().collect::<Vec<_>>(),
+            vec!["expiry_date"]
+        );
+        assert_eq!(masked.custom_fields[0].value, None);
+
+        assert_eq!(revealed.password.as_deref(), Some("4970100000000000"));
+        assert_eq!(revealed.fields["cvv"], "123");
+        assert_eq!(revealed.custom_fields[0].value.as_deref(), Some("4321"));
+
+        let json = serde_json::to_value(&masked).unwrap();
+        assert!(json.get("password").is_none());
+        assert_eq!(json["name"], "Visa");
+    }
+
+    #[test]
+    fn test_field_value() {
+        let entry = card();
+        let value = |field| field_value(&entry, field, "master").unwrap();
+
+        assert_eq!(value("password").as_deref(), Some("4970100000000000"));
+        assert_eq!(value("card_number").as_deref(), Some("4970100000000000"));
+        assert_eq!(value("cardholder").as_deref(), Some("John Smith"));
+        assert_eq!(value("cvv").as_deref(), Some("123"));
+        assert_eq!(value("Bank PIN").as_deref(), Some("4321"));
+        assert_eq!(value("otp"), None);
+        assert_eq!(value("unknown"), None);
+    }
+}
@@ -0,0 +1,286 @@
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Input, Select};
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::{write_local_config, CONFIG, LANGUAGE, PASSPHRASE_WORDS};
+use crate::generator::on_off;
+use crate::languages::get_translation;
+use crate::utils::clear_screen;
+
+// BIP-39 word lists: 2048 words each, none of which starts like another one.
+pub(crate) const ENGLISH_WORDS: &str = include_str!("../assets/wordlists/en.txt");
+pub(crate) const FRENCH_WORDS: &str = include_str!("../assets/wordlists/fr.txt");
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PassphraseOptions {
+    pub words: usize,
+    pub separator: String,
+    pub capitalize: bool,
+    // Adds a digit at the end of one of the words.
+    pub add_digit: bool,
+    // Language of the bundled word list. Defaults to the language of NeoPass.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    // A word list of your own, such as the EFF large word list: one word per line,
+    // optionally preceded by its dice roll.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wordlist_path: Option<PathBuf>,
+}
+
+impl Default for PassphraseOptions {
+    fn default() -> Self {
+        PassphraseOptions {
+            words: PASSPHRASE_WORDS,
+            separator: "-".to_string(),
+            capitalize: false,
+            add_digit: false,
+            language: None,
+            wordlist_path: None,
+        }
+    }
+}
+
+pub struct Passphrase {
+    pub value: String,
+    // Bits of entropy, for someone who knows the options and the word list.
+    pub entropy: f64,
+}
+
+/// Reads a word list, one word per line. Lines may start with dice rolls, as in the
+/// EFF lists, in which case the word is the last thing on the line.
+pub fn parse_wordlist(content: &str) -> Vec<String> {
+    let mut words: Vec<String> = content
+        .lines()
+        .filter_map(|line| line.split_whitespace().last())
+        .map(|word| word.to_string())
+        .collect();
+
+    // Duplicated words would make the entropy look higher than it is.
+    words.sort();
+    words.dedup();
+
+    words
+}
+
+pub fn load_wordlist(options: &PassphraseOptions) -> Result<Vec<String>, Box<dyn Error>> {
+    if let Some(path) = &options.wordlist_path {
+        let words = parse_wordlist(&fs::read_to_string(path)?);
+
+        if words.len() < 2 {
+            return Err(format!("{} is not a word list", path.display()).into());
+        }
+
+        return Ok(words);
+    }
+
+    let language = options
+        .language
+        .clone()
+        .unwrap_or_else(|| LANGUAGE.lock().unwrap().clone());
+
+    Ok(match language.as_str() {
+        "fr" => parse_wordlist(FRENCH_WORDS),
+        _ => parse_wordlist(ENGLISH_WORDS),
+    })
+}
+
+fn capitalize(word: &str) -> String {
+    let mut characters = word.chars();
+
+    match characters.next() {
+        Some(first) => first.to_uppercase().chain(characters).collect(),
+        None => String::new(),
+    }
+}
+
+pub fn generate_passphrase_with(
+    options: &PassphraseOptions,
+    wordlist: &[String],
+    rng: &mut impl Rng,
+) -> Passphrase {
+    let words = options.words.max(1);
+
+    let mut chosen: Vec<String> = (0..words)
+        .map(|_| wordlist.choose(rng).unwrap().clone())
+        .map(|word| {
+            if options.capitalize {
+                capitalize(&word)
+            } else {
+                word
+            }
+        })
+        .collect();
+
+    let mut entropy = words as f64 * (wordlist.len() as f64).log2();
+
+    if options.add_digit {
+        let index = rng.gen_range(0..words);
+        chosen[index].push(char::from(b'0' + rng.gen_range(0..10)));
+        entropy += (10.0 * words as f64).log2();
+    }
+
+    Passphrase {
+        value: chosen.join(&options.separator),
+        entropy,
+    }
+}
+
+pub fn generate_passphrase(options: &PassphraseOptions) -> Result<Passphrase, Box<dyn Error>> {
+    let wordlist = load_wordlist(options)?;
+
+    Ok(generate_passphrase_with(
+        options,
+        &wordlist,
+        &mut thread_rng(),
+    ))
+}
+
+/// Passphrase generator screen. `None` when the user leaves.
+pub fn generate_passphrase_interactively() -> Result<Option<String>, Box<dyn Error>> {
+    let mut options = CONFIG.lock().unwrap().passphrase.clone();
+    let mut selection = 0;
+
+    loop {
+        let passphrase = generate_passphrase(&options);
+
+        clear_screen()?;
+        println!("\n  {}\n", get_translation("passphrase_generator"));
+
+        match &passphrase {
+            Ok(passphrase) => println!(
+                "  {}\n\n  ≈ {:.0} {}\n",
+                style(&passphrase.value).bold(),
+                passphrase.entropy,
+                get_translation("bits_of_entropy")
+            ),
+            Err(error) => println!("  ❌ {}\n", error),
+        }
+
+        let items = [
+            get_translation("use_passphrase"),
+            get_translation("regenerate_passphrase"),
+            format!("{}: {}", get_translation("passphrase_words"), options.words),
+            format!(
+                "{}: \"{}\"",
+                get_translation("passphrase_separator"),
+                options.separator
+            ),
+            format!(
+                "{}: {}",
+                get_translation("passphrase_capitalize"),
+                on_off(options.capitalize)
+            ),
+            format!(
+                "{}: {}",
+                get_translation("passphrase_add_digit"),
+                on_off(options.add_digit)
+            ),
+            get_translation("passphrase_save_as_default"),
+        ];
+
+        selection = match Select::with_theme(&ColorfulTheme::default())
+            .default(selection)
+            .items(&items)
+            .interact_opt()?
+        {
+            Some(selection) => selection,
+            None => return Ok(None),
+        };
+
+        match selection {
+            0 => {
+                if let Ok(passphrase) = passphrase {
+                    return Ok(Some(passphrase.value));
+                }
+            }
+            1 => {}
+            2 => {
+                options.words = Input::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!("  {}", get_translation("passphrase_words")))
+                    .with_initial_text(options.words.to_string())
+                    .validate_with(|words: &usize| -> Result<(), String> {
+                        if *words == 0 {
+                            Err(get_translation("passphrase_no_words"))
+                        } else {
+                            Ok(())
+                        }
+                    })
+                    .interact_text()?
+            }
+            3 => {
+                options.separator = Input::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!("  {}", get_translation("passphrase_separator")))
+                    .with_initial_text(options.separator.clone())
+                    .allow_empty(true)
+                    .interact_text()?
+            }
+            4 => options.capitalize = !options.capitalize,
+            5 => options.add_digit = !options.add_digit,
+            _ => {
+                CONFIG.lock().unwrap().passphrase = options.clone();
+                write_local_config()?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::mock::StepRng;
+
+    fn wordlist() -> Vec<String> {
+        parse_wordlist("11111\tapple\n11112\tbanana\n11113\tcherry\n11114\tdate\n")
+    }
+
+    #[test]
+    fn test_bundled_wordlists() {
+        assert_eq!(parse_wordlist(ENGLISH_WORDS).len(), 2048);
+        assert_eq!(parse_wordlist(FRENCH_WORDS).len(), 2048);
+    }
+
+    #[test]
+    fn test_parse_wordlist() {
+        assert_eq!(wordlist(), vec!["apple", "banana", "cherry", "date"]);
+        assert_eq!(parse_wordlist("b\na\n\nb\n"), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_passphrase() {
+        let options = PassphraseOptions {
+            words: 5,
+            separator: " ".to_string(),
+            ..Default::default()
+        };
+
+        let passphrase = generate_passphrase_with(&options, &wordlist(), &mut thread_rng());
+
+        let words: Vec<&str> = passphrase.value.split(' ').collect();
+        assert_eq!(words.len(), 5);
+        assert!(words
+            .iter()
+            .all(|word| wordlist().contains(&word.to_string())));
+        assert_eq!(passphrase.entropy, 10.0);
+    }
+
+    #[test]
+    fn test_capitalize_and_digit() {
+        let options = PassphraseOptions {
+            words: 3,
+            capitalize: true,
+            add_digit: true,
+            ..Default::default()
+        };
+
+        let passphrase = generate_passphrase_with(&options, &wordlist(), &mut StepRng::new(0, 0));
+
+        assert_eq!(passphrase.value, "Apple0-Apple-Apple");
+        assert!((passphrase.entropy - (6.0 + 30f64.log2())).abs() < 1e-9);
+    }
+}
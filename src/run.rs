@@ -0,0 +1,363 @@
+use std::error::Error;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Text written by the child process instead of the secrets it prints.
+pub const MASK: &str = "<concealed by neopass>";
+
+/// An environment variable to set from the vault, written as
+/// `VAR=entry:"<name or identifier>".<field>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvReference {
+    pub variable: String,
+    pub entry: String,
+    pub field: String,
+}
+
+/// Parses an `--env` value. The name of the entry may be quoted, and contain dots;
+/// without a field, the password is used.
+pub fn parse_env_reference(value: &str) -> Result<EnvReference, String> {
+    let (variable, reference) = value
+        .split_once('=')
+        .ok_or("expected VAR=entry:\"<entry>\".<field>")?;
+    if variable.is_empty() {
+        return Err("the name of the variable is empty".to_string());
+    }
+
+    let reference = reference
+        .strip_prefix("entry:")
+        .ok_or("the value must start with entry:")?;
+
+    let (entry, field) = match reference.strip_prefix('"') {
+        Some(quoted) => {
+            let (entry, rest) = quoted.split_once('"').ok_or("unterminated quote")?;
+            match rest {
+                "" => (entry, "password"),
+                _ => (
+                    entry,
+                    rest.strip_prefix('.')
+                        .ok_or("expected a . after the quote")?,
+                ),
+            }
+        }
+        // Shells remove the quotes, so the field is what follows the last dot.
+        None => match reference.rsplit_once('.') {
+            Some((entry, field)) if !entry.is_empty() => (entry, field),
+            _ => (reference, "password"),
+        },
+    };
+
+    if entry.is_empty() || field.is_empty() {
+        return Err("the entry and its field cannot be empty".to_string());
+    }
+
+    Ok(EnvReference {
+        variable: variable.to_string(),
+        entry: entry.to_string(),
+        field: field.to_string(),
+    })
+}
+
+/// Replaces secrets in a stream written in chunks, where a secret may be split
+/// between two chunks.
+pub struct Masker {
+    secrets: Vec<Vec<u8>>,
+    pending: Vec<u8>,
+}
+
+impl Masker {
+    pub fn new(secrets: &[String]) -> Self {
+        let mut secrets: Vec<Vec<u8>> = secrets
+            .iter()
+            .filter(|secret| !secret.is_empty())
+            .map(|secret| secret.as_bytes().to_vec())
+            .collect();
+        // The longest first, in case a secret contains another one.
+        secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+        secrets.dedup();
+
+        Masker {
+            secrets,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Masks a chunk. What could be the start of a secret is held back until the
+    /// next chunk, or `finish`.
+    pub fn mask(&mut self, chunk: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(chunk);
+
+        let mut output = Vec::with_capacity(self.pending.len());
+        let mut position = 0;
+
+        while position < self.pending.len() {
+            let rest = &self.pending[position..];
+
+            if let Some(secret) = self.secrets.iter().find(|s| rest.starts_with(s)) {
+                output.extend_from_slice(MASK.as_bytes());
+                position += secret.len();
+            } else if self.secrets.iter().any(|s| s.starts_with(rest)) {
+                // Maybe a secret, the next chunk will tell.
+                break;
+            } else {
+                output.push(rest[0]);
+                position += 1;
+            }
+        }
+
+        self.pending.drain(..position);
+        output
+    }
+
+    pub fn finish(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+// Copies the output of the child, masked, as it comes.
+fn forward(
+    mut source: impl Read,
+    mut destination: impl Write,
+    secrets: &[String],
+) -> io::Result<()> {
+    let mut masker = Masker::new(secrets);
+    let mut buffer = [0u8; 8192];
+
+    loop {
+        let read = match source.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+        destination.write_all(&masker.mask(&buffer[..read]))?;
+        destination.flush()?;
+    }
+
+    destination.write_all(&masker.finish())?;
+    destination.flush()
+}
+
+// While the command runs, NeoPass stays alive to forward what it prints: Ctrl+C reaches
+// the command from the terminal and is ignored here, SIGTERM is passed on to it.
+#[cfg(unix)]
+mod signals {
+    use std::io;
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    static CHILD: AtomicI32 = AtomicI32::new(0);
+
+    extern "C" fn forward_signal(signal: libc::c_int) {
+        let child = CHILD.load(Ordering::SeqCst);
+        if child > 0 {
+            // SAFETY: kill is async-signal-safe.
+            unsafe { libc::kill(child, signal) };
+        }
+    }
+
+    /// Puts the previous handlers back when dropped.
+    pub struct SignalGuard {
+        interrupt: libc::sighandler_t,
+        terminate: libc::sighandler_t,
+    }
+
+    /// Installed before the command is spawned, so no signal is missed.
+    pub fn catch_signals(command: &mut Command) -> SignalGuard {
+        let handler = forward_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+
+        // SAFETY: both handlers only touch an atomic and call async-signal-safe functions.
+        let guard = unsafe {
+            SignalGuard {
+                interrupt: libc::signal(libc::SIGINT, libc::SIG_IGN),
+                terminate: libc::signal(libc::SIGTERM, handler),
+            }
+        };
+
+        // Ignored signals stay ignored in the command, unlike handled ones: it gets the
+        // handling NeoPass had before.
+        let interrupt = match guard.interrupt {
+            libc::SIG_IGN => libc::SIG_IGN,
+            _ => libc::SIG_DFL,
+        };
+        // SAFETY: only calls signal, which is async-signal-safe, in the forked process.
+        unsafe {
+            command.pre_exec(move || {
+                if libc::signal(libc::SIGINT, interrupt) == libc::SIG_ERR {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        guard
+    }
+
+    pub fn forward_signals_to(child: u32) {
+        CHILD.store(child as i32, Ordering::SeqCst);
+    }
+
+    impl Drop for SignalGuard {
+        fn drop(&mut self) {
+            CHILD.store(0, Ordering::SeqCst);
+            // SAFETY: puts back the handlers `signal` returned.
+            unsafe {
+                libc::signal(libc::SIGINT, self.interrupt);
+                libc::signal(libc::SIGTERM, self.terminate);
+            }
+        }
+    }
+}
+
+/// Runs `command` with `variables` set in its environment, masking their values in
+/// what it prints. Returns its exit code.
+pub fn run_with_secrets(
+    command: &[String],
+    variables: &[(String, String)],
+) -> Result<i32, Box<dyn Error>> {
+    let (program, arguments) = command.split_first().ok_or("No command to run")?;
+
+    let mut command = Command::new(program);
+    command
+        .args(arguments)
+        .envs(variables.iter().cloned())
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    #[cfg(unix)]
+    let _signals = signals::catch_signals(&mut command);
+
+    let mut child = command
+        .spawn()
+        .map_err(|error| format!("Cannot run {}: {}", program, error))?;
+
+    #[cfg(unix)]
+    signals::forward_signals_to(child.id());
+
+    let secrets: Vec<String> = variables.iter().map(|(_, value)| value.clone()).collect();
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+
+    let stdout_secrets = secrets.clone();
+    let stdout = thread::spawn(move || forward(stdout, io::stdout(), &stdout_secrets));
+    let stderr = thread::spawn(move || forward(stderr, io::stderr(), &secrets));
+
+    let status = child.wait()?;
+
+    // Processes started by the command may still write to its pipes.
+    let stdout = stdout.join().unwrap();
+    let stderr = stderr.join().unwrap();
+    stdout?;
+    stderr?;
+
+    if let Some(code) = status.code() {
+        return Ok(code);
+    }
+
+    // Killed by a signal, reported the way shells do.
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return Ok(128 + signal);
+        }
+    }
+
+    Ok(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Signal handlers belong to the whole process, commands are run one at a time.
+    static RUNNING: Mutex<()> = Mutex::new(());
+
+    fn mask_chunks(secrets: &[&str], chunks: &[&str]) -> String {
+        let secrets: Vec<String> = secrets.iter().map(|s| s.to_string()).collect();
+        let mut masker = Masker::new(&secrets);
+
+        let mut output = Vec::new();
+        for chunk in chunks {
+            output.extend(masker.mask(chunk.as_bytes()));
+        }
+        output.extend(masker.finish());
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_parse_env_reference() {
+        assert_eq!(
+            parse_env_reference("DB_PASS=entry:\"postgres prod\".password").unwrap(),
+            EnvReference {
+                variable: "DB_PASS".to_string(),
+                entry: "postgres prod".to_string(),
+                field: "password".to_string(),
+            }
+        );
+
+        let unquoted = parse_env_reference("USER=entry:example.com.username").unwrap();
+        assert_eq!(unquoted.entry, "example.com");
+        assert_eq!(unquoted.field, "username");
+
+        assert_eq!(
+            parse_env_reference("TOKEN=entry:\"GitHub\"").unwrap().field,
+            "password"
+        );
+        assert!(parse_env_reference("DB_PASS").is_err());
+        assert!(parse_env_reference("=entry:Mail").is_err());
+        assert!(parse_env_reference("DB_PASS=Mail.password").is_err());
+        assert!(parse_env_reference("DB_PASS=entry:\"Mail.password").is_err());
+    }
+
+    #[test]
+    fn test_masker() {
+        assert_eq!(
+            mask_chunks(&["hunter2"], &["password: hunter2\n"]),
+            format!("password: {}\n", MASK)
+        );
+        // Split between two chunks.
+        assert_eq!(
+            mask_chunks(&["hunter2"], &["password: hun", "ter2, hunt"]),
+            format!("password: {}, hunt", MASK)
+        );
+        assert_eq!(
+            mask_chunks(&["secret", "secret-token"], &["secret-token secret"]),
+            format!("{} {}", MASK, MASK)
+        );
+        assert_eq!(mask_chunks(&[""], &["nothing"]), "nothing");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_with_secrets() {
+        let command = vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "test \"$DB_PASS\" = hunter2 && exit 7".to_string(),
+        ];
+        let variables = vec![("DB_PASS".to_string(), "hunter2".to_string())];
+
+        let _running = RUNNING.lock().unwrap();
+        assert_eq!(run_with_secrets(&command, &variables).unwrap(), 7);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_interrupt_waits_for_the_command() {
+        // Without the signal handling, the interrupt would stop the tests.
+        let command = vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "kill -INT $PPID; sleep 0.1; exit 3".to_string(),
+        ];
+
+        let _running = RUNNING.lock().unwrap();
+        assert_eq!(run_with_secrets(&command, &[]).unwrap(), 3);
+    }
+}
@@ -12,11 +12,51 @@ use crate::{paging::Paging, render::TermThemeRenderer};
 #[derive(Debug)]
 pub enum SelectOutput {
     Copy(usize),
+    CopyCode(usize),
     Add,
     Delete(usize),
     Edit(usize),
+    Details(usize),
+    Attachments(usize),
+    PasswordHistory(usize),
     ChangeLanguage,
     ChangeMasterPassword,
+    RestoreBackup,
+    SwitchVault,
+    Trash,
+    Undo,
+    Audit,
+}
+
+// THIS IS NEW.
+impl SelectOutput {
+    /// Whether this action needs to write the vault.
+    pub fn modifies_vault(&self) -> bool {
+        matches!(
+            self,
+            SelectOutput::Add
+                | SelectOutput::Delete(_)
+                | SelectOutput::Edit(_)
+                | SelectOutput::ChangeMasterPassword
+                | SelectOutput::RestoreBackup
+                | SelectOutput::Trash
+                | SelectOutput::Undo
+        )
+    }
+
+    /// Replaces the index of the selected row by the one of its entry.
+    pub fn map_index(self, f: impl Fn(usize) -> usize) -> Self {
+        match self {
+            SelectOutput::Copy(index) => SelectOutput::Copy(f(index)),
+            SelectOutput::CopyCode(index) => SelectOutput::CopyCode(f(index)),
+            SelectOutput::Delete(index) => SelectOutput::Delete(f(index)),
+            SelectOutput::Edit(index) => SelectOutput::Edit(f(index)),
+            SelectOutput::Details(index) => SelectOutput::Details(f(index)),
+            SelectOutput::Attachments(index) => SelectOutput::Attachments(f(index)),
+            SelectOutput::PasswordHistory(index) => SelectOutput::PasswordHistory(f(index)),
+            other => other,
+        }
+    }
 }
 
 /// Renders a select prompt.
@@ -149,16 +189,15 @@ impl Select<'_> {
             return Err(io::Error::new(io::ErrorKind::NotConnected, "not a terminal").into());
         }
 
-        if self.items.is_empty() {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Empty list of items given to `Select`",
-            ))?;
-        }
-
+        // THIS IS NEW. An empty list still accepts the keys that need no item, such as
+        // adding an entry or opening the trash.
         let mut paging = Paging::new(term, self.items.len(), self.max_length);
         let mut render = TermThemeRenderer::new(term, self.theme);
-        let mut sel = self.default;
+        let mut sel = if self.items.is_empty() {
+            !0
+        } else {
+            self.default
+        };
 
         let mut size_vec = Vec::new();
 
@@ -197,7 +236,7 @@ impl Select<'_> {
             term.flush()?;
 
             match term.read_key()? {
-                Key::ArrowDown | Key::Tab | Key::Char('j') => {
+                Key::ArrowDown | Key::Tab | Key::Char('j') if !self.items.is_empty() => {
                     if sel == !0 {
                         sel = 0;
                     } else {
@@ -218,7 +257,7 @@ impl Select<'_> {
                         return Ok(None);
                     }
                 }
-                Key::ArrowUp | Key::BackTab => {
+                Key::ArrowUp | Key::BackTab if !self.items.is_empty() => {
                     if sel == !0 {
                         sel = self.items.len() - 1;
                     } else {
@@ -255,6 +294,17 @@ impl Select<'_> {
                     return Ok(Some(SelectOutput::Copy(sel)));
                 }
                 // THIS IS NEW.
+                Key::Char('o') | Key::Char('O') if sel != !0 => {
+                    if self.clear {
+                        render.clear()?;
+                    }
+
+                    term.show_cursor()?;
+                    term.flush()?;
+
+                    return Ok(Some(SelectOutput::CopyCode(sel)));
+                }
+                // THIS IS NEW.
                 Key::Char('a') | Key::Char('A') => {
                     if self.clear {
                         render.clear()?;
@@ -288,6 +338,72 @@ impl Select<'_> {
                     return Ok(Some(SelectOutput::Edit(sel)));
                 }
                 // THIS IS NEW.
+                Key::Char('i') | Key::Char('I') if sel != !0 => {
+                    if self.clear {
+                        render.clear()?;
+                    }
+
+                    term.show_cursor()?;
+                    term.flush()?;
+
+                    return Ok(Some(SelectOutput::Details(sel)));
+                }
+                // THIS IS NEW.
+                Key::Char('f') | Key::Char('F') if sel != !0 => {
+                    if self.clear {
+                        render.clear()?;
+                    }
+
+                    term.show_cursor()?;
+                    term.flush()?;
+
+                    return Ok(Some(SelectOutput::Attachments(sel)));
+                }
+                // THIS IS NEW.
+                Key::Char('h') | Key::Char('H') if sel != !0 => {
+                    if self.clear {
+                        render.clear()?;
+                    }
+
+                    term.show_cursor()?;
+                    term.flush()?;
+
+                    return Ok(Some(SelectOutput::PasswordHistory(sel)));
+                }
+                // THIS IS NEW.
+                Key::Char('t') | Key::Char('T') => {
+                    if self.clear {
+                        render.clear()?;
+                    }
+
+                    term.show_cursor()?;
+                    term.flush()?;
+
+                    return Ok(Some(SelectOutput::Trash));
+                }
+                // THIS IS NEW.
+                Key::Char('u') | Key::Char('U') => {
+                    if self.clear {
+                        render.clear()?;
+                    }
+
+                    term.show_cursor()?;
+                    term.flush()?;
+
+                    return Ok(Some(SelectOutput::Undo));
+                }
+                // THIS IS NEW.
+                Key::Char('s') | Key::Char('S') => {
+                    if self.clear {
+                        render.clear()?;
+                    }
+
+                    term.show_cursor()?;
+                    term.flush()?;
+
+                    return Ok(Some(SelectOutput::Audit));
+                }
+                // THIS IS NEW.
                 Key::Char('l') | Key::Char('L') => {
                     if self.clear {
                         render.clear()?;
@@ -309,6 +425,28 @@ impl Select<'_> {
 
                     return Ok(Some(SelectOutput::ChangeMasterPassword));
                 }
+                // THIS IS NEW.
+                Key::Char('r') | Key::Char('R') => {
+                    if self.clear {
+                        render.clear()?;
+                    }
+
+                    term.show_cursor()?;
+                    term.flush()?;
+
+                    return Ok(Some(SelectOutput::RestoreBackup));
+                }
+                // THIS IS NEW.
+                Key::Char('v') | Key::Char('V') => {
+                    if self.clear {
+                        render.clear()?;
+                    }
+
+                    term.show_cursor()?;
+                    term.flush()?;
+
+                    return Ok(Some(SelectOutput::SwitchVault));
+                }
                 _ => {}
             }
 
@@ -0,0 +1,820 @@
+use chrono::{Datelike, Utc};
+use console::{style, Key, Term};
+use dialoguer::theme::{ColorfulTheme, Theme};
+use dialoguer::Confirm;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io;
+
+use crate::languages::get_translation;
+use crate::passphrase::{ENGLISH_WORDS, FRENCH_WORDS};
+
+// Most common passwords first: the rank of a password is its line number.
+const COMMON_PASSWORDS: &str = include_str!("../assets/dictionaries/passwords.txt");
+
+// Guesses per second of an offline attack against a slow hash, such as the key
+// derivation protecting the vault.
+const GUESSES_PER_SECOND: f64 = 1e4;
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_MULTI_CHAR: f64 = 50.0;
+// Every pattern added to a guess makes the attacker try more combinations.
+const MIN_GUESSES_BEFORE_GROWING_SEQUENCE: f64 = 1e4;
+const MIN_YEAR_SPACE: f64 = 20.0;
+// Longer passwords are only estimated on their beginning, which is strong enough.
+const MAX_ANALYZED_LENGTH: usize = 100;
+const MAX_WORD_LENGTH: usize = 32;
+// Passwords below this score are only accepted once confirmed.
+pub const MIN_ACCEPTED_SCORE: u8 = 2;
+
+const LEET_SUBSTITUTIONS: [(char, &str); 10] = [
+    ('4', "a"),
+    ('@', "a"),
+    ('8', "b"),
+    ('3', "e"),
+    ('1', "il"),
+    ('!', "i"),
+    ('|', "il"),
+    ('0', "o"),
+    ('$', "s"),
+    ('5', "s"),
+];
+
+/// How hard a password is to guess, following the approach of zxcvbn: the password is
+/// split into the patterns an attacker would try first (common passwords and words,
+/// keyboard walks, repeats, sequences, dates) and the cheapest split wins.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Strength {
+    pub guesses: f64,
+    // From 0 (guessed right away) to 4 (out of reach).
+    pub score: u8,
+    pub crack_seconds: f64,
+}
+
+struct Keyboard {
+    // Row and horizontal position of every key, for unshifted characters.
+    keys: HashMap<char, (i32, f64)>,
+    shifted: HashMap<char, char>,
+    average_degree: f64,
+}
+
+impl Keyboard {
+    fn new(rows: &[(&str, f64)], unshifted: &str, shifted: &str) -> Keyboard {
+        let mut keys = HashMap::new();
+        for (row, (characters, offset)) in rows.iter().enumerate() {
+            for (column, character) in characters.chars().enumerate() {
+                keys.insert(character, (row as i32, offset + column as f64));
+            }
+        }
+
+        let shifted = shifted.chars().zip(unshifted.chars()).collect();
+
+        let mut keyboard = Keyboard {
+            keys,
+            shifted,
+            average_degree: 0.0,
+        };
+
+        let degrees: usize = keyboard
+            .keys
+            .keys()
+            .map(|a| {
+                keyboard
+                    .keys
+                    .keys()
+                    .filter(|b| keyboard.direction(*a, **b).is_some())
+                    .count()
+            })
+            .sum();
+        keyboard.average_degree = degrees as f64 / keyboard.keys.len() as f64;
+
+        keyboard
+    }
+
+    fn unshift(&self, character: char) -> (char, bool) {
+        if let Some(unshifted) = self.shifted.get(&character) {
+            return (*unshifted, true);
+        }
+        if character.is_uppercase() {
+            let lowercase = character.to_lowercase().next().unwrap_or(character);
+            if self.keys.contains_key(&lowercase) {
+                return (lowercase, true);
+            }
+        }
+        (character, false)
+    }
+
+    // Direction from key `a` to key `b`, when they are next to each other.
+    fn direction(&self, a: char, b: char) -> Option<(i32, i32)> {
+        let (row_a, x_a) = self.keys.get(&a)?;
+        let (row_b, x_b) = self.keys.get(&b)?;
+        let rows = row_b - row_a;
+        let x = x_b - x_a;
+
+        let adjacent = match rows {
+            0 => (x.abs() - 1.0).abs() < 0.01,
+            -1 | 1 => x.abs() < 1.0,
+            _ => false,
+        };
+
+        adjacent.then_some((rows, if x < 0.0 { -1 } else { 1 }))
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref DICTIONARIES: Vec<HashMap<String, usize>> = vec![
+        ranked(COMMON_PASSWORDS.lines()),
+        ranked(ENGLISH_WORDS.lines()),
+        ranked(FRENCH_WORDS.lines()),
+    ];
+    static ref KEYBOARDS: Vec<Keyboard> = vec![
+        Keyboard::new(
+            &[
+                ("`1234567890-=", 0.0),
+                ("qwertyuiop[]\\", 1.5),
+                ("asdfghjkl;'", 1.75),
+                ("zxcvbnm,./", 2.25),
+            ],
+            "`1234567890-=[]\\;',./",
+            "~!@#$%^&*()_+{}|:\"<>?",
+        ),
+        Keyboard::new(
+            &[
+                ("²&é\"'(-è_çà)=", 0.0),
+                ("azertyuiop^$", 1.5),
+                ("qsdfghjklmù*", 1.75),
+                ("<wxcvbn,;:!", 1.25),
+            ],
+            "&é\"'(-è_çà)=,;:!<",
+            "1234567890°+?./§>",
+        ),
+    ];
+}
+
+fn ranked<'a>(words: impl Iterator<Item = &'a str>) -> HashMap<String, usize> {
+    let mut ranks = HashMap::new();
+    for (index, word) in words.map(str::trim).filter(|w| !w.is_empty()).enumerate() {
+        ranks.entry(word.to_lowercase()).or_insert(index + 1);
+    }
+    ranks
+}
+
+// Words taken from the entry itself, such as its name and username, and their parts.
+fn user_dictionary(user_inputs: &[&str]) -> HashMap<String, usize> {
+    let mut words: Vec<String> = Vec::new();
+    for input in user_inputs {
+        let input = input.trim().to_lowercase();
+        words.extend(
+            input
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|part| part.chars().count() > 2)
+                .map(str::to_string),
+        );
+        words.push(input);
+    }
+    ranked(words.iter().map(String::as_str))
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    (0..k).fold(1.0, |result, i| result * (n - i) as f64 / (i + 1) as f64)
+}
+
+fn factorial(n: usize) -> f64 {
+    (1..=n).fold(1.0, |result, i| result * i as f64)
+}
+
+// Ways of mixing `changed` characters among `unchanged` ones, for capitals and such.
+fn variations(changed: usize, unchanged: usize) -> f64 {
+    if changed == 0 || unchanged == 0 {
+        return 2.0;
+    }
+    (1..=changed.min(unchanged))
+        .map(|i| binomial(changed + unchanged, i))
+        .sum()
+}
+
+fn uppercase_variations(token: &[char]) -> f64 {
+    let upper = token.iter().filter(|c| c.is_uppercase()).count();
+    let lower = token.iter().filter(|c| c.is_lowercase()).count();
+
+    if upper == 0 {
+        return 1.0;
+    }
+    // Capitalized first or last letter, or shouting.
+    let first_only = token[0].is_uppercase() && upper == 1;
+    let last_only = token[token.len() - 1].is_uppercase() && upper == 1;
+    if first_only || last_only || lower == 0 {
+        return 2.0;
+    }
+
+    (1..=upper.min(lower))
+        .map(|i| binomial(upper + lower, i))
+        .sum()
+}
+
+// Lowercase versions of a token with its l33t characters turned back into letters.
+fn unleet(token: &str) -> Vec<String> {
+    let mut candidates = vec![String::new()];
+
+    for character in token.chars() {
+        let letters = LEET_SUBSTITUTIONS
+            .iter()
+            .find(|(leet, _)| *leet == character)
+            .map(|(_, letters)| *letters);
+
+        candidates = match letters {
+            Some(letters) => candidates
+                .iter()
+                .flat_map(|candidate| {
+                    letters
+                        .chars()
+                        .map(move |letter| format!("{}{}", candidate, letter))
+                })
+                .collect(),
+            None => candidates
+                .into_iter()
+                .map(|candidate| format!("{}{}", candidate, character))
+                .collect(),
+        };
+
+        // Enough to read common substitutions without exploding on long tokens.
+        candidates.truncate(4);
+    }
+
+    candidates.retain(|candidate| candidate != token);
+    candidates
+}
+
+fn leet_variations(token: &[char], word: &str) -> f64 {
+    let mut result = 1.0;
+
+    for (leet, original) in token.iter().zip(word.chars()) {
+        if *leet == original || !LEET_SUBSTITUTIONS.iter().any(|(l, _)| l == leet) {
+            continue;
+        }
+        let substituted = token.iter().filter(|c| *c == leet).count();
+        let unchanged = token.iter().filter(|c| **c == original).count();
+        result *= variations(substituted, unchanged);
+    }
+
+    result
+}
+
+fn dictionary_matches(
+    password: &[char],
+    user_dictionary: &HashMap<String, usize>,
+) -> Vec<(usize, usize, f64)> {
+    let mut matches = Vec::new();
+    let dictionaries: Vec<&HashMap<String, usize>> =
+        DICTIONARIES.iter().chain([user_dictionary]).collect();
+
+    let rank = |word: &str| -> Option<usize> {
+        dictionaries
+            .iter()
+            .filter_map(|dictionary| dictionary.get(word))
+            .min()
+            .copied()
+    };
+
+    for start in 0..password.len() {
+        for end in start + 1..=password.len().min(start + MAX_WORD_LENGTH) {
+            let token = &password[start..end];
+            let lowercase: String = token.iter().collect::<String>().to_lowercase();
+            let capitals = uppercase_variations(token);
+
+            if let Some(rank) = rank(&lowercase) {
+                matches.push((start, end, rank as f64 * capitals));
+            }
+
+            if token.len() > 2 {
+                let reversed: String = lowercase.chars().rev().collect();
+                if reversed != lowercase {
+                    if let Some(rank) = rank(&reversed) {
+                        matches.push((start, end, rank as f64 * capitals * 2.0));
+                    }
+                }
+            }
+
+            for word in unleet(&lowercase) {
+                if let Some(rank) = rank(&word) {
+                    let leet = leet_variations(&lowercase.chars().collect::<Vec<_>>(), &word);
+                    matches.push((start, end, rank as f64 * capitals * leet));
+                }
+            }
+        }
+    }
+
+    matches
+}
+
+fn spatial_guesses(keyboard: &Keyboard, length: usize, turns: usize, shifted: usize) -> f64 {
+    let starting_positions = keyboard.keys.len() as f64;
+    let mut guesses = 0.0;
+
+    for i in 2..=length {
+        for j in 1..=turns.min(i - 1) {
+            guesses += binomial(i - 1, j - 1)
+                * starting_positions
+                * keyboard.average_degree.powi(j as i32);
+        }
+    }
+
+    if shifted > 0 {
+        guesses *= variations(shifted, length - shifted);
+    }
+
+    guesses
+}
+
+// Runs of neighbouring keys, such as "qwerty" or "zxcvbn".
+fn spatial_matches(password: &[char]) -> Vec<(usize, usize, f64)> {
+    let mut matches = Vec::new();
+
+    for keyboard in KEYBOARDS.iter() {
+        let keys: Vec<(char, bool)> = password.iter().map(|c| keyboard.unshift(*c)).collect();
+        let mut start = 0;
+
+        while start < keys.len() {
+            let mut end = start + 1;
+            let mut turns = 0;
+            let mut last_direction = None;
+
+            while end < keys.len() {
+                match keyboard.direction(keys[end - 1].0, keys[end].0) {
+                    Some(direction) => {
+                        if last_direction != Some(direction) {
+                            turns += 1;
+                            last_direction = Some(direction);
+                        }
+                        end += 1;
+                    }
+                    None => break,
+                }
+            }
+
+            if end - start >= 3 {
+                let shifted = keys[start..end].iter().filter(|(_, s)| *s).count();
+                matches.push((
+                    start,
+                    end,
+                    spatial_guesses(keyboard, end - start, turns, shifted),
+                ));
+            }
+
+            start = end;
+        }
+    }
+
+    matches
+}
+
+// Characters following each other in the alphabet or among digits, such as "abcd" or "4321".
+fn sequence_matches(password: &[char]) -> Vec<(usize, usize, f64)> {
+    let mut matches = Vec::new();
+    let class = |c: char| {
+        (
+            c.is_ascii_lowercase(),
+            c.is_ascii_uppercase(),
+            c.is_ascii_digit(),
+        )
+    };
+    let mut start = 0;
+
+    while start + 1 < password.len() {
+        let delta = password[start + 1] as i32 - password[start] as i32;
+        let mut end = start + 1;
+
+        while end < password.len()
+            && (delta == 1 || delta == -1)
+            && password[end] as i32 - password[end - 1] as i32 == delta
+            && class(password[end]) == class(password[start])
+            && password[start].is_ascii_alphanumeric()
+        {
+            end += 1;
+        }
+
+        if end - start >= 3 {
+            let first = password[start];
+            let mut base = if "aAzZ019".contains(first) {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            if delta < 0 {
+                base *= 2.0;
+            }
+            matches.push((start, end, base * (end - start) as f64));
+            start = end - 1;
+        } else {
+            start += 1;
+        }
+    }
+
+    matches
+}
+
+// Tokens made of a repeated base, such as "aaaa" or "abcabc".
+fn repeat_matches(
+    password: &[char],
+    user_dictionary: &HashMap<String, usize>,
+) -> Vec<(usize, usize, f64)> {
+    let mut matches = Vec::new();
+
+    for start in 0..password.len() {
+        let mut best: Option<(usize, usize)> = None;
+
+        for base_length in 1..=(password.len() - start) / 2 {
+            let base = &password[start..start + base_length];
+            let mut count = 1;
+            while start + (count + 1) * base_length <= password.len()
+                && password[start + count * base_length..start + (count + 1) * base_length] == *base
+            {
+                count += 1;
+            }
+
+            let length = count * base_length;
+            if count >= 2 && length >= 3 && best.is_none_or(|(_, l)| length > l) {
+                best = Some((base_length, length));
+            }
+        }
+
+        if let Some((base_length, length)) = best {
+            let base = &password[start..start + base_length];
+            let count = length / base_length;
+            matches.push((
+                start,
+                start + length,
+                minimum_guesses(base, user_dictionary) * count as f64,
+            ));
+        }
+    }
+
+    matches
+}
+
+fn year_guesses(year: i32) -> f64 {
+    ((year - Utc::now().year()).abs() as f64).max(MIN_YEAR_SPACE)
+}
+
+fn as_year(digits: &str) -> Option<i32> {
+    let value: i32 = digits.parse().ok()?;
+    match digits.len() {
+        2 if value > 50 => Some(1900 + value),
+        2 => Some(2000 + value),
+        4 if (1000..=2050).contains(&value) => Some(value),
+        _ => None,
+    }
+}
+
+// Guesses for a date made of three numbers, in any common order.
+fn date_guesses(parts: &[&str]) -> Option<f64> {
+    let is_day = |part: &str| part.len() <= 2 && matches!(part.parse::<u32>(), Ok(1..=31));
+    let is_month = |part: &str| part.len() <= 2 && matches!(part.parse::<u32>(), Ok(1..=12));
+
+    let orders = [(0, 1, 2), (0, 2, 1), (2, 1, 0), (2, 0, 1)];
+    orders.iter().find_map(|(year, month, day)| {
+        let year = as_year(parts[*year])?;
+        (is_month(parts[*month]) && is_day(parts[*day])).then(|| year_guesses(year) * 365.0)
+    })
+}
+
+fn date_matches(password: &[char]) -> Vec<(usize, usize, f64)> {
+    let mut matches = Vec::new();
+
+    for start in 0..password.len() {
+        for end in start + 4..=password.len().min(start + 10) {
+            let token: String = password[start..end].iter().collect();
+
+            if token.chars().all(|c| c.is_ascii_digit()) {
+                if token.len() == 4 {
+                    if let Some(year) = as_year(&token).filter(|y| (1900..=2050).contains(y)) {
+                        matches.push((start, end, year_guesses(year)));
+                    }
+                }
+
+                // Without separators, every way of cutting the digits is tried.
+                if token.len() <= 8 {
+                    let cuts = (1..token.len() - 1)
+                        .flat_map(|a| (a + 1..token.len()).map(move |b| (a, b)))
+                        .filter_map(|(a, b)| {
+                            date_guesses(&[&token[..a], &token[a..b], &token[b..]])
+                        });
+                    if let Some(guesses) = cuts.reduce(f64::min) {
+                        matches.push((start, end, guesses));
+                    }
+                }
+                continue;
+            }
+
+            let separator = match token.chars().find(|c| !c.is_ascii_digit()) {
+                Some(separator) if " /-._".contains(separator) => separator,
+                _ => continue,
+            };
+            let parts: Vec<&str> = token.split(separator).collect();
+            if parts.len() == 3
+                && parts
+                    .iter()
+                    .all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+            {
+                if let Some(guesses) = date_guesses(&parts) {
+                    matches.push((start, end, guesses * 4.0));
+                }
+            }
+        }
+    }
+
+    matches
+}
+
+fn bruteforce_guesses(length: usize) -> f64 {
+    let minimum = if length == 1 {
+        MIN_SUBMATCH_GUESSES_SINGLE_CHAR
+    } else {
+        MIN_SUBMATCH_GUESSES_MULTI_CHAR
+    };
+    BRUTEFORCE_CARDINALITY
+        .powi(length as i32)
+        .max(minimum + 1.0)
+}
+
+// Guesses needed for the cheapest way of building `password` out of patterns.
+fn minimum_guesses(password: &[char], user_dictionary: &HashMap<String, usize>) -> f64 {
+    let length = password.len();
+    if length == 0 {
+        return 1.0;
+    }
+
+    let mut matches = dictionary_matches(password, user_dictionary);
+    matches.extend(spatial_matches(password));
+    matches.extend(sequence_matches(password));
+    matches.extend(repeat_matches(password, user_dictionary));
+    matches.extend(date_matches(password));
+
+    let mut by_end: Vec<Vec<(usize, f64)>> = vec![Vec::new(); length + 1];
+    for (start, end, guesses) in matches {
+        // A pattern within a longer password still takes a few guesses.
+        let minimum = if end - start == length {
+            1.0
+        } else if end - start == 1 {
+            MIN_SUBMATCH_GUESSES_SINGLE_CHAR
+        } else {
+            MIN_SUBMATCH_GUESSES_MULTI_CHAR
+        };
+        by_end[end].push((start, guesses.max(minimum)));
+    }
+    for (end, matches) in by_end.iter_mut().enumerate() {
+        for start in 0..end {
+            matches.push((start, bruteforce_guesses(end - start)));
+        }
+    }
+
+    // best[end][count]: fewest guesses covering the first `end` characters with
+    // `count` patterns.
+    let mut best = vec![vec![f64::INFINITY; length + 1]; length + 1];
+    best[0][0] = 1.0;
+    for end in 1..=length {
+        for (start, guesses) in &by_end[end] {
+            for count in 0..=*start {
+                let candidate = best[*start][count] * guesses;
+                if candidate < best[end][count + 1] {
+                    best[end][count + 1] = candidate;
+                }
+            }
+        }
+    }
+
+    (1..=length)
+        .map(|count| {
+            factorial(count) * best[length][count]
+                + MIN_GUESSES_BEFORE_GROWING_SEQUENCE.powi(count as i32 - 1)
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// Estimates the strength of a password. `user_inputs` are words an attacker would
+/// try first for this password, such as the name of the entry and its username.
+pub fn estimate(password: &str, user_inputs: &[&str]) -> Strength {
+    let characters: Vec<char> = password.chars().take(MAX_ANALYZED_LENGTH).collect();
+    let guesses = minimum_guesses(&characters, &user_dictionary(user_inputs));
+
+    let score = match guesses {
+        g if g < 1e3 + 5.0 => 0,
+        g if g < 1e6 + 5.0 => 1,
+        g if g < 1e8 + 5.0 => 2,
+        g if g < 1e10 + 5.0 => 3,
+        _ => 4,
+    };
+
+    Strength {
+        guesses,
+        score,
+        crack_seconds: guesses / GUESSES_PER_SECOND,
+    }
+}
+
+pub fn format_duration(seconds: f64) -> String {
+    const UNITS: [(f64, &str, &str); 6] = [
+        (1.0, "duration_second", "duration_seconds"),
+        (60.0, "duration_minute", "duration_minutes"),
+        (3600.0, "duration_hour", "duration_hours"),
+        (86400.0, "duration_day", "duration_days"),
+        (2629800.0, "duration_month", "duration_months"),
+        (31557600.0, "duration_year", "duration_years"),
+    ];
+
+    if seconds < 1.0 {
+        return get_translation("duration_instant");
+    }
+    if seconds >= 100.0 * 31557600.0 {
+        return get_translation("duration_centuries");
+    }
+
+    let (length, singular, plural) = UNITS
+        .iter()
+        .rev()
+        .find(|(length, _, _)| seconds >= *length)
+        .unwrap();
+    let count = (seconds / length).round() as u64;
+
+    format!(
+        "{} {}",
+        count,
+        get_translation(if count == 1 { singular } else { plural })
+    )
+}
+
+/// One line describing the strength of a password, such as "●●○○○ Weak · ...".
+pub fn describe_strength(strength: &Strength) -> String {
+    let labels = [
+        "strength_very_weak",
+        "strength_weak",
+        "strength_fair",
+        "strength_strong",
+        "strength_very_strong",
+    ];
+    let score = strength.score as usize;
+    let gauge = format!("{}{}", "●".repeat(score + 1), "○".repeat(4 - score));
+    let gauge = match strength.score {
+        0 | 1 => style(gauge).red(),
+        2 => style(gauge).yellow(),
+        _ => style(gauge).green(),
+    };
+
+    format!(
+        "{} {}  ·  {} {}",
+        gauge,
+        get_translation(labels[score]),
+        get_translation("cracked_in"),
+        format_duration(strength.crack_seconds)
+    )
+}
+
+// Reads a concealed line, displaying the strength of what is typed underneath.
+fn read_with_strength(term: &Term, prompt: &str, user_inputs: &[&str]) -> io::Result<String> {
+    let theme = ColorfulTheme::default();
+    let mut input = String::new();
+
+    loop {
+        let mut line = String::new();
+        theme
+            .format_password_prompt(&mut line, prompt)
+            .map_err(io::Error::other)?;
+        term.write_line(&format!("{}{}", line, "*".repeat(input.chars().count())))?;
+        if input.is_empty() {
+            term.write_line("")?;
+        } else {
+            term.write_line(&format!(
+                "    {}",
+                describe_strength(&estimate(&input, user_inputs))
+            ))?;
+        }
+
+        let key = term.read_key()?;
+        term.clear_last_lines(2)?;
+
+        match key {
+            Key::Enter => break,
+            Key::Backspace => {
+                input.pop();
+            }
+            Key::CtrlC => return Err(io::Error::from(io::ErrorKind::Interrupted)),
+            Key::Char(character) if !character.is_control() => input.push(character),
+            _ => {}
+        }
+    }
+
+    let mut line = String::new();
+    theme
+        .format_password_prompt_selection(&mut line, prompt)
+        .map_err(io::Error::other)?;
+    term.write_line(&line)?;
+
+    Ok(input)
+}
+
+/// Asks for a new password, showing its strength as it is typed. Weak passwords are
+/// only accepted once the user confirms them.
+pub fn ask_new_password(
+    prompt: &str,
+    user_inputs: &[&str],
+    allow_empty: bool,
+) -> Result<String, Box<dyn Error>> {
+    let term = Term::stderr();
+
+    loop {
+        let password = read_with_strength(&term, prompt, user_inputs)?;
+
+        if password.is_empty() {
+            if allow_empty {
+                return Ok(password);
+            }
+            continue;
+        }
+
+        let strength = estimate(&password, user_inputs);
+        if strength.score >= MIN_ACCEPTED_SCORE {
+            return Ok(password);
+        }
+
+        let accepted = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("  {}", get_translation("use_weak_password")))
+            .default(false)
+            .interact()?;
+        if accepted {
+            return Ok(password);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(password: &str) -> u8 {
+        estimate(password, &[]).score
+    }
+
+    #[test]
+    fn test_weak_passwords() {
+        for password in [
+            "123",
+            "password",
+            "P@ssw0rd",
+            "Password1",
+            "qwertyuiop",
+            "azertyuiop",
+            "zxcvbnm,./",
+            "aaaaaaaaaaaa",
+            "abcabcabcabc",
+            "abcdefghij",
+            "9876543210",
+            "19/04/1987",
+            "19041987",
+            "drowssap",
+        ] {
+            assert!(score(password) <= 1, "{}", password);
+        }
+    }
+
+    #[test]
+    fn test_strong_passwords() {
+        assert_eq!(score("h7#Kq9!zP2@wLm4x"), 4);
+        assert_eq!(score("abandon-gorilla-vessel-timber-oyster-lunar"), 4);
+    }
+
+    #[test]
+    fn test_guesses_grow_with_length() {
+        let short = estimate("tK8#", &[]).guesses;
+        let long = estimate("tK8#pQ2&", &[]).guesses;
+
+        assert!(long > short * 1000.0);
+    }
+
+    #[test]
+    fn test_user_inputs() {
+        let without = estimate("Johnathan.Smithers1984", &[]);
+        let with = estimate("Johnathan.Smithers1984", &["johnathan.smithers@mail.com"]);
+
+        assert!(with.guesses < without.guesses);
+    }
+
+    #[test]
+    fn test_patterns() {
+        assert!(!spatial_matches(&"xqwertx".chars().collect::<Vec<_>>()).is_empty());
+        assert_eq!(
+            sequence_matches(&"x1234x".chars().collect::<Vec<_>>())
+                .iter()
+                .map(|(start, end, _)| (*start, *end))
+                .collect::<Vec<_>>(),
+            vec![(1, 5)]
+        );
+        assert!(date_matches(&"x2023-12-25".chars().collect::<Vec<_>>())
+            .iter()
+            .any(|(start, end, _)| (*start, *end) == (1, 11)));
+    }
+}
@@ -0,0 +1,184 @@
+use chrono::{Local, Utc};
+use console::Term;
+use dialoguer::{theme::ColorfulTheme, Confirm, Select};
+use std::error::Error;
+
+use crate::config::CONFIG;
+use crate::entry::Entry;
+use crate::languages::get_translation;
+
+/// Indices of the entries that are not in the trash, in the order they are displayed.
+pub fn visible_entries(entries: &[Entry]) -> Vec<usize> {
+    (0..entries.len())
+        .filter(|index| !entries[*index].is_in_trash())
+        .collect()
+}
+
+pub fn trashed_entries(entries: &[Entry]) -> Vec<usize> {
+    (0..entries.len())
+        .filter(|index| entries[*index].is_in_trash())
+        .collect()
+}
+
+pub fn move_to_trash(entries: &mut [Entry], index: usize) {
+    entries[index].deleted_at = Some(Utc::now());
+}
+
+pub fn restore_from_trash(entries: &mut [Entry], index: usize) {
+    entries[index].deleted_at = None;
+}
+
+/// Deletes an entry for good. The content of its attachments is deleted once the
+/// vault is saved.
+pub fn purge(entries: &mut Vec<Entry>, index: usize) {
+    entries.remove(index);
+}
+
+/// Asks before moving an entry to the trash, when the config file says so.
+pub fn confirm_deletion(entry: &Entry) -> Result<bool, Box<dyn Error>> {
+    if !CONFIG.lock().unwrap().confirm_deletions {
+        return Ok(true);
+    }
+
+    Ok(Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "  {} {} ({})?",
+            get_translation("confirm_move_to_trash"),
+            entry.application,
+            entry.username
+        ))
+        .default(false)
+        .interact()?)
+}
+
+fn confirm(prompt: &str) -> Result<bool, Box<dyn Error>> {
+    Ok(Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("  {}", get_translation(prompt)))
+        .default(false)
+        .interact()?)
+}
+
+/// Trash screen of the interactive interface. Returns whether the entries changed.
+pub fn manage_trash(entries: &mut Vec<Entry>) -> Result<bool, Box<dyn Error>> {
+    let mut changed = false;
+
+    loop {
+        let trashed = trashed_entries(entries);
+
+        println!("\n  {}\n", get_translation("trash"));
+
+        if trashed.is_empty() {
+            println!("  {}", get_translation("trash_is_empty"));
+            Term::stderr().read_key()?;
+            return Ok(changed);
+        }
+
+        let mut items: Vec<String> = trashed
+            .iter()
+            .map(|index| {
+                let entry = &entries[*index];
+                format!(
+                    "{} ({})  ·  {} {}",
+                    entry.application,
+                    entry.username,
+                    get_translation("deleted_at"),
+                    entry
+                        .deleted_at
+                        .unwrap_or_default()
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M")
+                )
+            })
+            .collect();
+        items.push(get_translation("empty_trash"));
+
+        let selection = match Select::with_theme(&ColorfulTheme::default())
+            .default(0)
+            .items(&items)
+            .interact_opt()?
+        {
+            Some(selection) => selection,
+            None => return Ok(changed),
+        };
+
+        if selection == trashed.len() {
+            if confirm("confirm_empty_trash")? {
+                // From the last one, so the remaining indices stay valid.
+                for index in trashed.into_iter().rev() {
+                    purge(entries, index);
+                }
+                changed = true;
+            }
+            continue;
+        }
+
+        let index = trashed[selection];
+        let actions = [
+            get_translation("restore_entry"),
+            get_translation("purge_entry"),
+        ];
+
+        match Select::with_theme(&ColorfulTheme::default())
+            .default(0)
+            .items(&actions)
+            .interact_opt()?
+        {
+            Some(0) => {
+                restore_from_trash(entries, index);
+                changed = true;
+            }
+            Some(_) if confirm("confirm_purge")? => {
+                purge(entries, index);
+                changed = true;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::build_rows;
+    use std::collections::BTreeMap;
+
+    fn entry(application: &str) -> Entry {
+        Entry::new(
+            application.to_string(),
+            "john".to_string(),
+            "secret".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_trash_and_restore() {
+        let mut entries = vec![entry("Mail"), entry("Bank"), entry("Shop")];
+
+        move_to_trash(&mut entries, 1);
+
+        assert_eq!(visible_entries(&entries), vec![0, 2]);
+        assert_eq!(trashed_entries(&entries), vec![1]);
+
+        restore_from_trash(&mut entries, 1);
+
+        assert_eq!(visible_entries(&entries), vec![0, 1, 2]);
+        assert!(trashed_entries(&entries).is_empty());
+    }
+
+    #[test]
+    fn test_trash_the_last_entry() {
+        let mut entries = vec![entry("Mail")];
+
+        move_to_trash(&mut entries, 0);
+
+        // The vault is not empty, the table is shown without rows.
+        assert!(visible_entries(&entries).is_empty());
+        assert!(!entries.is_empty());
+
+        let (rows, header, footer) = build_rows(&entries, &[], &BTreeMap::new(), &None, &None);
+
+        assert!(rows.is_empty());
+        assert_eq!(header.lines().count(), 2);
+        assert!(footer.starts_with("  ╰"));
+    }
+}
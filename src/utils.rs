@@ -1,80 +1,204 @@
+use chrono::Utc;
 use clipboard::{ClipboardContext, ClipboardProvider};
 use cocoon::Cocoon;
 use console::Term;
-use std::io::{Error, ErrorKind};
-use std::{error::Error as ErrorTrait, fs::File};
-use tabled::settings::object::Rows;
+use std::io::Error;
+use std::{
+    collections::BTreeMap,
+    error::Error as ErrorTrait,
+    fmt,
+    fs::{self, File},
+    path::Path,
+};
+use tabled::settings::object::{Cell, Columns, Rows};
 use tabled::settings::{Alignment, Disable, Style};
 use tabled::Table;
 
-use dialoguer::{theme::ColorfulTheme, Password};
+use dialoguer::{theme::ColorfulTheme, Confirm, Password, Select};
 
-use crate::config::FILE_PATH;
-use crate::entry::{add_a_new_entry, Entry};
+use crate::attachments::collect_unused_blobs;
+use crate::backups::take_snapshot;
+use crate::config::{vault_path, vault_sibling_path};
+use crate::derived::{entry_password, store_derived_passwords};
+use crate::entry::{add_a_new_entry, has_expiry_date, table_headers, Entry, EXPIRY_COLUMN};
+use crate::kinds::EntryKind;
 use crate::languages::get_translation;
+use crate::lock::{check_vault_is_writable, is_read_only, remember_vault_state, VaultLockError};
+use crate::strength::ask_new_password;
+use crate::vault::{deserialize_entries, serialize_entries, VAULT_VERSION};
+use crate::vaults::current_vault_label;
 
 pub fn decrypt_file(password: &str) -> Result<Vec<Entry>, Box<dyn ErrorTrait>> {
-    let mut entries = Vec::new();
-    let mut cocoon = Cocoon::new(password.as_bytes());
+    let path = vault_path();
 
     // Read the contents of the password file.
-    if let Ok(mut input_file) = File::open(FILE_PATH) {
-        let encoded = match cocoon.parse(&mut input_file) {
-            Ok(encoded) => encoded,
-            Err(_) => {
-                return Err(Box::new(Error::new(ErrorKind::Other, "Invalid password")));
-            }
-        };
-
-        let lines = std::str::from_utf8(&encoded)?
-            .split('\n')
-            .collect::<Vec<&str>>();
-
-        for line in lines {
-            let parts: Vec<&str> = line.split(',').collect();
-            if parts.len() == 3 {
-                entries.push(Entry {
-                    application: parts[0].trim().to_string(),
-                    username: parts[1].trim().to_string(),
-                    password: parts[2].trim().to_string(),
-                });
-            }
+    if path.exists() {
+        let (entries, version) = read_vault_file(&path, password)?;
+        remember_vault_state(&path)?;
+
+        // Keep the file as it was before upgrading it to the current format.
+        if version < VAULT_VERSION && !is_read_only() {
+            fs::copy(&path, vault_sibling_path(&format!(".v{}.bak", version)))?;
+            write_entries_in_file(&entries, password)?;
         }
+
+        Ok(entries)
     } else {
         // Create password file.
-        let mut file = File::create(FILE_PATH)?;
-        let contents = "BEGIN".to_string();
-        cocoon.dump(contents.into_bytes(), &mut file).unwrap();
+        write_entries_in_file(&[], password)?;
+
+        Ok(Vec::new())
     }
+}
+
+/// The master password does not decrypt the vault.
+#[derive(Debug)]
+pub struct InvalidPasswordError;
 
-    Ok(entries)
+impl fmt::Display for InvalidPasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid password")
+    }
 }
 
-pub fn write_entries_in_file(
-    entries: &Vec<Entry>,
+impl ErrorTrait for InvalidPasswordError {}
+
+// Decrypts any vault file, returning its entries and the version it was stored with.
+pub fn read_vault_file(
+    path: &Path,
     password: &str,
-) -> Result<(), Box<dyn ErrorTrait>> {
-    let mut contents = String::new();
-    for entry in entries {
-        contents.push_str(&format!(
-            "{},{},{}\n",
-            entry.application, entry.username, entry.password
-        ));
+) -> Result<(Vec<Entry>, u32), Box<dyn ErrorTrait>> {
+    let cocoon = Cocoon::new(password.as_bytes());
+    let mut input_file = File::open(path)?;
+
+    let encoded = match cocoon.parse(&mut input_file) {
+        Ok(encoded) => encoded,
+        Err(_) => return Err(Box::new(InvalidPasswordError)),
+    };
+
+    deserialize_entries(&encoded)
+}
+
+/// Decrypts a vault file with the current master password, or asks for its own
+/// password when it was written with another one.
+pub fn unlock_vault_file(
+    path: &Path,
+    password: &str,
+    prompt: &str,
+) -> Result<Vec<Entry>, Box<dyn ErrorTrait>> {
+    if let Ok((entries, _)) = read_vault_file(path, password) {
+        return Ok(entries);
     }
 
-    encrypt_file(contents, password)
+    let file_password = Password::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("  {}", prompt))
+        .interact()?;
+
+    Ok(read_vault_file(path, &file_password)?.0)
 }
 
-pub fn encrypt_file(contents: String, password: &str) -> Result<(), Box<dyn ErrorTrait>> {
+pub fn write_entries_in_file(entries: &[Entry], password: &str) -> Result<(), Box<dyn ErrorTrait>> {
+    write_vault(entries, password)?;
+    take_snapshot()?;
+
+    // Unused attachments only waste space, and are collected again on the next save.
+    let _ = collect_unused_blobs(entries, password);
+
+    Ok(())
+}
+
+// Writes the vault without taking a snapshot of it.
+fn write_vault(entries: &[Entry], password: &str) -> Result<(), Box<dyn ErrorTrait>> {
+    let contents = serialize_entries(entries)?;
+
+    check_vault_is_writable(&vault_path())?;
+    encrypt_file(contents, password)?;
+    remember_vault_state(&vault_path())
+}
+
+/// Saves the entries edited in the interactive interface. When the vault cannot be
+/// written, the user is told why and the entries are reloaded from the vault.
+pub fn save_entries(entries: &mut Vec<Entry>, password: &str) -> Result<(), Box<dyn ErrorTrait>> {
+    match write_entries_in_file(entries, password) {
+        Ok(()) => Ok(()),
+        Err(error) => reload_after_lock_error(error, entries, password),
+    }
+}
+
+fn lock_error_message(error: &(dyn ErrorTrait + 'static)) -> Option<String> {
+    match error.downcast_ref::<VaultLockError>()? {
+        VaultLockError::ReadOnly => Some(get_translation("vault_read_only")),
+        VaultLockError::ModifiedExternally => Some(get_translation("vault_modified")),
+    }
+}
+
+// When another instance of NeoPass keeps the vault from being written, tells the user
+// and reads the vault again, dropping the change. Other errors are returned.
+fn reload_after_lock_error(
+    error: Box<dyn ErrorTrait>,
+    entries: &mut Vec<Entry>,
+    password: &str,
+) -> Result<(), Box<dyn ErrorTrait>> {
+    let message = match lock_error_message(error.as_ref()) {
+        Some(message) => message,
+        None => return Err(error),
+    };
+
+    println!("\n  {}", message);
+    Term::stderr().read_key()?;
+
+    *entries = decrypt_file(password)?;
+
+    Ok(())
+}
+
+pub fn encrypt_file(contents: Vec<u8>, password: &str) -> Result<(), Box<dyn ErrorTrait>> {
     let mut cocoon = Cocoon::new(password.as_bytes());
+    let path = vault_path();
+    let temp_path = vault_sibling_path(".tmp");
+
+    // Write the encrypted contents to a temporary file first, so that a crash
+    // or a full disk never leaves a truncated vault behind.
+    let mut file = File::create(&temp_path)?;
+
+    if let Err(error) = cocoon.dump(contents, &mut file) {
+        fs::remove_file(&temp_path)?;
+        return Err(Box::new(Error::other(format!(
+            "Cannot encrypt the vault: {:?}",
+            error
+        ))));
+    }
 
-    // Write the encrypted contents to the output file.
-    let mut file = File::create(FILE_PATH)?;
-    cocoon.dump(contents.into_bytes(), &mut file).unwrap();
+    file.sync_all()?;
+    drop(file);
+
+    // Keep the previous version of the vault around.
+    if path.exists() {
+        fs::copy(&path, vault_sibling_path(".bak"))?;
+    }
+
+    fs::rename(&temp_path, &path)?;
+    sync_parent_directory(&path)?;
 
     Ok(())
 }
 
+// Makes the rename above durable. Directories cannot be opened this way on Windows.
+#[cfg(unix)]
+fn sync_parent_directory(path: &Path) -> Result<(), Error> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    File::open(parent)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_parent_directory(_path: &Path) -> Result<(), Error> {
+    Ok(())
+}
+
 pub fn clear_screen() -> Result<(), Error> {
     // Clean and get cursor back on top.
     Term::stderr().clear_last_lines(30)
@@ -85,14 +209,36 @@ pub fn display_instructions() {
         ["  ↓     ", &get_translation("down_arrow")],
         ["  ↑     ", &get_translation("up_arrow")],
         ["  a     ", &get_translation("add_entry")],
-        ["  d     ", &get_translation("delete_entry")],
+        ["  d     ", &get_translation("move_to_trash")],
         ["  e     ", &get_translation("edit_entry")],
         ["  Space ", &get_translation("copy_password")],
+        ["  o     ", &get_translation("copy_one_time_code")],
+        ["  i     ", &get_translation("entry_details")],
+        ["  f     ", &get_translation("manage_attachments")],
+        ["  h     ", &get_translation("show_password_history")],
+        ["  u     ", &get_translation("undo")],
+        ["  t     ", &get_translation("show_trash")],
+        ["  s     ", &get_translation("security_audit")],
         ["  l     ", &get_translation("change_language")],
         ["  p     ", &get_translation("change_master_password")],
+        ["  r     ", &get_translation("restore_backup")],
+        ["  v     ", &get_translation("switch_vault")],
     ]);
     table.with(Style::blank()).with(Disable::row(Rows::first()));
-    println!("\n  {}\n{}\n", get_translation("commands"), table);
+    let read_only = if is_read_only() {
+        get_translation("read_only")
+    } else {
+        "".to_string()
+    };
+
+    println!(
+        "\n  {} {} {}\n\n  {}\n{}\n",
+        get_translation("current_vault"),
+        current_vault_label(),
+        read_only,
+        get_translation("commands"),
+        table
+    );
 }
 
 pub fn get_user_password(
@@ -105,16 +251,28 @@ pub fn get_user_password(
     println!();
 
     while !password_is_correct {
-        let msg: String = if File::open(FILE_PATH).is_ok() {
+        // Another instance is creating this vault, with its own master password.
+        if !vault_path().exists() && is_read_only() {
+            clear_screen()?;
+            println!("\n  {}\n", get_translation("vault_being_created"));
+            Term::stderr().read_key()?;
+            continue;
+        }
+
+        let msg: String = if vault_path().exists() {
             get_translation("enter_password")
         } else {
             get_translation("enter_password_first_time")
         };
 
-        *password = Password::with_theme(&ColorfulTheme::default())
-            .with_prompt(format!("  {}", msg))
-            .interact()
-            .unwrap();
+        *password = if vault_path().exists() {
+            Password::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("  {}", msg))
+                .interact()
+                .unwrap()
+        } else {
+            ask_new_password(&format!("  {}", msg), &[], false)?
+        };
 
         println!("\n  {}", get_translation("checking_password"));
 
@@ -124,9 +282,13 @@ pub fn get_user_password(
                 password_is_correct = true;
                 *entries = found_entries;
             }
-            Err(_) => {
+            Err(error) => {
                 clear_screen()?;
-                println!("\n  {}\n", get_translation("invalid_password"));
+                println!(
+                    "\n  {}\n",
+                    lock_error_message(error.as_ref())
+                        .unwrap_or_else(|| get_translation("invalid_password"))
+                );
                 continue;
             }
         };
@@ -142,23 +304,56 @@ pub fn add_first_entry(
     println!("\n  {}", get_translation("no_password"));
 
     add_a_new_entry(entries);
-    write_entries_in_file(entries, password)?;
+    save_entries(entries, password)?;
 
     Ok(())
 }
 
-pub fn build_rows(entries: &[Entry], copied_item: &Option<usize>) -> (Vec<String>, String, String) {
+/// Rows of the table of the entries at the `visible` indices.
+pub fn build_rows(
+    entries: &[Entry],
+    visible: &[usize],
+    breached: &BTreeMap<usize, u64>,
+    copied_item: &Option<usize>,
+    copied_code: &Option<String>,
+) -> (Vec<String>, String, String) {
     // Build table.
-    let mut table = Table::new(entries.iter().map(|e| Entry {
-        application: e.application.clone(),
-        username: e.username.clone(),
-        password: "********".to_string(),
+    let mut table = Table::new(visible.iter().map(|index| {
+        let e = &entries[*index];
+        Entry {
+            password: if e.derivation.is_some() {
+                get_translation("derived")
+            } else if e.password.is_empty() {
+                String::new()
+            } else if breached.contains_key(index) {
+                format!("******** ⚠ {}", get_translation("breached"))
+            } else {
+                "********".to_string()
+            },
+            ..e.clone()
+        }
     }));
 
     let table = table
         .with(Style::rounded())
         .modify(Rows::new(1..), Alignment::left());
 
+    // Columns are named after what the kinds of the entries shown hold.
+    let kinds: Vec<EntryKind> = visible.iter().map(|index| entries[*index].kind).collect();
+    for (column, header) in table_headers(&kinds).into_iter().enumerate() {
+        table.modify(Cell::new(0, column), header);
+    }
+
+    // Columns are removed from the last one, so the others keep their index.
+    if !has_expiry_date(&kinds) {
+        table.with(Disable::column(Columns::single(EXPIRY_COLUMN)));
+    }
+
+    // The kind of the entries is only worth a column when they are not all logins.
+    if kinds.iter().all(EntryKind::is_login) {
+        table.with(Disable::column(Columns::single(1)));
+    }
+
     let table_as_string = table.to_string();
 
     // Get table rows so we can make them selectable.
@@ -167,17 +362,27 @@ pub fn build_rows(entries: &[Entry], copied_item: &Option<usize>) -> (Vec<String
         .map(|e| e.into())
         .collect::<Vec<String>>();
 
-    let header = format!(
-        "  {}\n  {}\n  {}",
-        rows.remove(0),
-        rows.remove(0),
-        rows.remove(0)
-    );
+    // Without entries, the line under the header closes the table.
+    let header_len = if visible.is_empty() { 2 } else { 3 };
+    let header = rows
+        .drain(..header_len)
+        .map(|row| format!("  {}", row))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let mut bottom = rows.remove(rows.len() - 1);
+    if visible.is_empty() {
+        bottom = bottom.replace('├', "╰").replace('┼', "┴").replace('┤', "╯");
+    }
 
     let footer = format!(
         "  {}\n\n  {}",
-        rows.remove(rows.len() - 1),
-        if copied_item.is_some() {
+        bottom,
+        if visible.is_empty() {
+            get_translation("no_visible_entry")
+        } else if let Some(message) = copied_code {
+            message.clone()
+        } else if copied_item.is_some() {
             get_translation("password_copied")
         } else {
             "".to_string()
@@ -190,35 +395,148 @@ pub fn build_rows(entries: &[Entry], copied_item: &Option<usize>) -> (Vec<String
 pub fn set_password_in_clipboard(
     entries: &[Entry],
     index: usize,
+    master_password: &str,
     copied_item: &mut Option<usize>,
 ) -> Result<(), Box<dyn ErrorTrait>> {
-    let entry = &entries[index];
+    let password = entry_password(&entries[index], master_password)?;
 
     // Copy password to clipboard.
     let mut cp: ClipboardContext = ClipboardProvider::new()?;
-    cp.set_contents(entry.password.clone())?;
+    cp.set_contents(password)?;
 
     *copied_item = Some(index);
 
     Ok(())
 }
 
+/// Copies the one-time code of an entry to the clipboard. Returns whether the entry
+/// changed and needs to be saved, which is the case of HOTP counters.
+pub fn copy_one_time_code(
+    entries: &mut [Entry],
+    index: usize,
+    copied_code: &mut Option<String>,
+) -> Result<bool, Box<dyn ErrorTrait>> {
+    let otp = match entries[index].otp.as_mut() {
+        Some(otp) => otp,
+        None => {
+            *copied_code = Some(get_translation("no_one_time_code"));
+            return Ok(false);
+        }
+    };
+
+    let (code, message, changed) = match otp.current_code() {
+        Some((code, remaining)) => (
+            code,
+            format!(
+                "{} {} {} s.",
+                get_translation("code_copied"),
+                get_translation("valid_for"),
+                remaining
+            ),
+            false,
+        ),
+        None => match otp.next_code() {
+            Some(code) => (code, get_translation("code_copied"), true),
+            None => return Err(Box::new(Error::other("Invalid one-time password secret"))),
+        },
+    };
+
+    let mut cp: ClipboardContext = ClipboardProvider::new()?;
+    cp.set_contents(code)?;
+
+    *copied_code = Some(message);
+
+    Ok(changed)
+}
+
+/// Remembers when the password of an entry was last copied.
+///
+/// This is not worth a snapshot of the vault, and is silently skipped when the
+/// vault cannot be written.
+pub fn mark_entry_as_used(
+    entries: &mut [Entry],
+    index: usize,
+    password: &str,
+) -> Result<(), Box<dyn ErrorTrait>> {
+    entries[index].last_used_at = Some(Utc::now());
+
+    match write_vault(entries, password) {
+        Err(error) if error.downcast_ref::<VaultLockError>().is_none() => Err(error),
+        _ => Ok(()),
+    }
+}
+
+/// Returns whether the master password changed.
 pub fn change_master_password(
     entries: &mut Vec<Entry>,
     password: &mut String,
-) -> Result<(), Box<dyn ErrorTrait>> {
+) -> Result<bool, Box<dyn ErrorTrait>> {
     println!();
 
-    *password = Password::with_theme(&ColorfulTheme::default())
-        .with_prompt(format!("  {}", get_translation("enter_new_password")))
-        .interact()
-        .unwrap();
+    // Derived passwords depend on the master password: the user would be locked out
+    // of these accounts unless they are stored first.
+    let derived = entries.iter().filter(|e| e.derivation.is_some()).count();
+    let mut store_derived = false;
+    if derived > 0 {
+        println!(
+            "  ⚠ {} ({})\n",
+            get_translation("derived_passwords_will_change"),
+            derived
+        );
+
+        let choices = [
+            get_translation("store_derived_passwords"),
+            get_translation("keep_derived_passwords"),
+            get_translation("cancel"),
+        ];
+        match Select::with_theme(&ColorfulTheme::default())
+            .default(0)
+            .items(&choices)
+            .interact_opt()?
+        {
+            Some(0) => store_derived = true,
+            Some(1)
+                if Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!(
+                        "  {}",
+                        get_translation("confirm_derived_passwords_change")
+                    ))
+                    .default(false)
+                    .interact()? => {}
+            _ => return Ok(false),
+        }
+    }
+
+    let new_password = ask_new_password(
+        &format!("  {}", get_translation("enter_new_password")),
+        &[],
+        false,
+    )?;
 
     println!("\n  {}", get_translation("checking_password"));
 
-    write_entries_in_file(entries, password)?;
+    // The entries and the vault stay as they were until the vault is written with the
+    // new password.
+    let mut new_entries = entries.clone();
+    if store_derived {
+        store_derived_passwords(&mut new_entries, password)?;
+    }
 
-    Ok(())
+    if let Err(error) = write_entries_in_file(&new_entries, &new_password) {
+        reload_after_lock_error(error, entries, password)?;
+        return Ok(false);
+    }
+    *entries = new_entries;
+    *password = new_password;
+
+    Ok(true)
+}
+
+pub fn display_read_only_warning(has_tried_to_modify_vault: &mut bool) {
+    if *has_tried_to_modify_vault {
+        println!("\n  {}", get_translation("vault_read_only"));
+        *has_tried_to_modify_vault = false;
+    }
 }
 
 pub fn display_password_change(has_changed_master_password: &mut bool) {
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+
+use crate::entry::Entry;
+use crate::migrations::{document_version, legacy_document, migrate};
+
+// Version of the document stored inside the encrypted vault.
+// Bump it whenever an older NeoPass would lose data by saving the vault, such as a new
+// field it does not know about: it then refuses the vault instead. Every bump needs a
+// step in `migrations::MIGRATIONS`, which does nothing when no data has to be
+// transformed.
+pub const VAULT_VERSION: u32 = 11;
+
+#[derive(Serialize, Deserialize)]
+pub struct VaultDocument {
+    pub version: u32,
+    pub entries: Vec<Entry>,
+}
+
+pub fn serialize_entries(entries: &[Entry]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let document = VaultDocumentRef {
+        version: VAULT_VERSION,
+        entries,
+    };
+
+    Ok(serde_json::to_vec(&document)?)
+}
+
+/// Reads the decrypted vault and upgrades it to the current version.
+///
+/// Also returns the version the vault was stored with, so the caller knows
+/// when it has to be saved again.
+pub fn deserialize_entries(contents: &[u8]) -> Result<(Vec<Entry>, u32), Box<dyn Error>> {
+    let contents = std::str::from_utf8(contents)?;
+
+    let document: Value = if contents.trim_start().starts_with('{') {
+        serde_json::from_str(contents)?
+    } else {
+        legacy_document(contents)
+    };
+
+    let version = document_version(&document)?;
+    let document: VaultDocument = serde_json::from_value(migrate(document)?)?;
+
+    Ok((document.entries, version))
+}
+
+// Borrowing twin of `VaultDocument` so saving does not clone every entry.
+#[derive(Serialize)]
+struct VaultDocumentRef<'a> {
+    version: u32,
+    entries: &'a [Entry],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::CustomField;
+    use crate::kinds::EntryKind;
+
+    fn entry(application: &str, username: &str, password: &str) -> Entry {
+        Entry::new(
+            application.to_string(),
+            username.to_string(),
+            password.to_string(),
+        )
+    }
+
+    #[test]
+    fn test_round_trip_keeps_commas_and_newlines() {
+        let entries = vec![
+            entry("Bank, Inc.", "john", "pa,ss\nword"),
+            entry("Mail", "john@doe.com", "  spaces  "),
+        ];
+
+        let contents = serialize_entries(&entries).unwrap();
+        let (parsed, version) = deserialize_entries(&contents).unwrap();
+
+        assert_eq!(parsed, entries);
+        assert_eq!(version, VAULT_VERSION);
+    }
+
+    #[test]
+    fn test_round_trip_keeps_details() {
+        let mut details = entry("Mail", "john", "secret");
+        details.urls = vec!["https://mail.com".to_string()];
+        details.notes = "First line\nSecond line".to_string();
+        details.tags = ["personal".to_string()].into_iter().collect();
+        details.custom_fields = vec![CustomField {
+            name: "PIN".to_string(),
+            value: "1234".to_string(),
+            concealed: true,
+        }];
+        let mut card = entry("Visa", "John Doe", "4111111111111111");
+        card.kind = EntryKind::Card;
+        card.fields
+            .insert("expiry_date".to_string(), "04/27".to_string());
+        let entries = vec![details, card, entry("Bank", "john", "1234")];
+
+        let contents = serialize_entries(&entries).unwrap();
+
+        assert_eq!(deserialize_entries(&contents).unwrap().0, entries);
+    }
+
+    #[test]
+    fn test_document_carries_version() {
+        let contents = serialize_entries(&[]).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&contents).unwrap();
+
+        assert_eq!(value["version"], VAULT_VERSION);
+    }
+
+    #[test]
+    fn test_legacy_payload() {
+        let (parsed, version) =
+            deserialize_entries(b"Mail,john@doe.com,secret\nbroken line\n").unwrap();
+
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].application, "Mail");
+        assert_eq!(parsed[0].username, "john@doe.com");
+        assert_eq!(parsed[0].password, "secret");
+        assert_eq!(parsed[0].created_at, None);
+        assert_eq!(version, 0);
+    }
+
+    #[test]
+    fn test_newer_version_is_refused() {
+        let contents = format!("{{\"version\":{},\"entries\":[]}}", VAULT_VERSION + 1);
+
+        assert!(deserialize_entries(contents.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,218 @@
+use console::Term;
+use dialoguer::{theme::ColorfulTheme, Input, Select};
+use std::error::Error;
+use std::path::PathBuf;
+
+use crate::config::{
+    default_vault_path, open_vault, vault_path, write_local_config, Config, VaultSettings, CONFIG,
+    CURRENT_VAULT, FILE_NAME,
+};
+use crate::entry::Entry;
+use crate::languages::get_translation;
+use crate::utils::{clear_screen, get_user_password};
+
+/// Names of vaults are used to name their files in the data directory, next to the
+/// default vault.
+pub fn check_vault_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("A vault needs a name".to_string());
+    }
+
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err("The name of a vault can only contain letters, digits, - and _".to_string());
+    }
+
+    let default_name = FILE_NAME.trim_end_matches(".txt");
+    if name.eq_ignore_ascii_case(default_name) {
+        return Err(format!("{} is the name of the default vault", default_name));
+    }
+
+    if CONFIG.lock().unwrap().vaults.contains_key(name) {
+        return Err(format!("A vault named {} already exists", name));
+    }
+
+    Ok(())
+}
+
+/// Adds a vault to the registry. Without a path, its file is created in the data directory.
+pub fn register_vault(name: &str, path: Option<PathBuf>) -> Result<PathBuf, Box<dyn Error>> {
+    check_vault_name(name)?;
+
+    let mut config = CONFIG.lock().unwrap();
+
+    let path = path.unwrap_or_else(|| default_vault_path().with_file_name(format!("{}.txt", name)));
+
+    config.vaults.insert(
+        name.to_string(),
+        VaultSettings {
+            path: path.clone(),
+            backup_retention: None,
+            password_history_retention: None,
+            inactivity_delay: None,
+        },
+    );
+    drop(config);
+
+    write_local_config()?;
+
+    Ok(path)
+}
+
+/// Removes a vault from the registry. Its file is left untouched.
+pub fn unregister_vault(name: &str) -> Result<(), Box<dyn Error>> {
+    let mut config = CONFIG.lock().unwrap();
+
+    if config.vaults.remove(name).is_none() {
+        return Err(format!("No vault named {}", name).into());
+    }
+
+    if config.default_vault.as_deref() == Some(name) {
+        config.default_vault = None;
+    }
+    drop(config);
+
+    write_local_config()
+}
+
+pub fn set_default_vault(name: &str) -> Result<(), Box<dyn Error>> {
+    let mut config = CONFIG.lock().unwrap();
+
+    if !config.vaults.contains_key(name) {
+        return Err(format!("No vault named {}", name).into());
+    }
+
+    config.default_vault = Some(name.to_string());
+    drop(config);
+
+    write_local_config()
+}
+
+/// Name of the vault currently open, or its path when it is not registered.
+pub fn current_vault_label() -> String {
+    match CURRENT_VAULT.lock().unwrap().clone() {
+        Some(name) => name,
+        None => vault_path().display().to_string(),
+    }
+}
+
+// Vaults the user can switch to: the unnamed default one first, so it can be opened
+// again, then the registered ones.
+fn vault_choices(config: &Config) -> Vec<(Option<String>, PathBuf)> {
+    let default_path = config.vault_path.clone().unwrap_or_else(default_vault_path);
+
+    std::iter::once((None, default_path))
+        .chain(
+            config
+                .vaults
+                .iter()
+                .map(|(name, settings)| (Some(name.clone()), settings.path.clone())),
+        )
+        .collect()
+}
+
+pub fn switch_vault(entries: &mut Vec<Entry>, password: &mut String) -> Result<(), Box<dyn Error>> {
+    println!("\n  {}", get_translation("select_vault"));
+
+    let vaults = vault_choices(&CONFIG.lock().unwrap());
+    let current_vault = (CURRENT_VAULT.lock().unwrap().clone(), vault_path());
+
+    let mut items: Vec<String> = vaults
+        .iter()
+        .map(|(name, path)| {
+            let name = match name {
+                Some(name) => name.clone(),
+                None => get_translation("default_vault"),
+            };
+            format!("{}  ·  {}", name, path.display())
+        })
+        .collect();
+    items.push(get_translation("new_vault"));
+
+    let selection = match Select::with_theme(&ColorfulTheme::default())
+        .default(
+            vaults
+                .iter()
+                .position(|vault| *vault == current_vault)
+                .unwrap_or_default(),
+        )
+        .items(&items)
+        .interact_opt()?
+    {
+        Some(selection) => selection,
+        None => return Ok(()),
+    };
+
+    let (name, path) = match vaults.get(selection) {
+        Some(vault) if *vault == current_vault => return Ok(()),
+        Some((name, path)) => (name.clone(), path.clone()),
+        None => {
+            let name: String = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("  {}", get_translation("enter_vault_name")))
+                .validate_with(|name: &String| check_vault_name(name))
+                .interact_text()?;
+            match register_vault(&name, None) {
+                Ok(path) => (Some(name), path),
+                Err(error) => {
+                    println!("\n  {}", error);
+                    Term::stderr().read_key()?;
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    open_vault(name, path)?;
+
+    // Every vault has its own master password.
+    clear_screen()?;
+    get_user_password(entries, password)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_vault_name() {
+        assert!(check_vault_name("work").is_ok());
+        assert!(check_vault_name("side-project_2").is_ok());
+
+        assert!(check_vault_name("").is_err());
+        assert!(check_vault_name("../work").is_err());
+        assert!(check_vault_name("my vault").is_err());
+        assert!(check_vault_name("passwords").is_err());
+        assert!(check_vault_name("Passwords").is_err());
+    }
+
+    #[test]
+    fn test_vault_choices() {
+        let mut config = Config::default();
+        config.vaults.insert(
+            "work".to_string(),
+            VaultSettings {
+                path: PathBuf::from("/vaults/work.txt"),
+                backup_retention: None,
+                password_history_retention: None,
+                inactivity_delay: None,
+            },
+        );
+
+        assert_eq!(
+            vault_choices(&config),
+            vec![
+                (None, default_vault_path()),
+                (Some("work".to_string()), PathBuf::from("/vaults/work.txt")),
+            ]
+        );
+
+        // A vault path set in the config file replaces the default one.
+        config.vault_path = Some(PathBuf::from("/vaults/passwords.txt"));
+        assert_eq!(
+            vault_choices(&config)[0],
+            (None, PathBuf::from("/vaults/passwords.txt"))
+        );
+    }
+}